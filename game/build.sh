@@ -1,8 +1,20 @@
 #!/bin/sh
+# `./build.sh [deploy] parallel` lets the benchmark worker rasterize on several threads, it needs nightly with
+# rust-src to rebuild std with atomics
+if [ "$1" = parallel ] || [ "$2" = parallel ]; then
+  export RUSTFLAGS='-C target-feature=+atomics,+bulk-memory,+mutable-globals'
+  TOOLCHAIN='+nightly'
+  PARALLEL_FLAGS='--features parallel -Z build-std=panic_abort,std'
+fi
+# Recorded with every submitted run
+GAME_BUILD="$(git describe --always --dirty 2>/dev/null || echo unknown)"
+export GAME_BUILD
 if [ "$1" = deploy ]; then
-  cargo build --profile deploy
+  cargo $TOOLCHAIN build --profile deploy $PARALLEL_FLAGS
   wasm-bindgen --target web --no-typescript --remove-name-section --remove-producers-section --out-dir ../www/ target/wasm32-unknown-unknown/deploy/game.wasm
 else
-  cargo build --release
+  cargo $TOOLCHAIN build --release $PARALLEL_FLAGS
   wasm-bindgen --target web --no-typescript --remove-producers-section --out-dir ../www/ target/wasm32-unknown-unknown/release/game.wasm
 fi
+mkdir -p ../www/assets
+cp assets/* ../www/assets/
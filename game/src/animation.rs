@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use gltf::animation::util::ReadOutputs;
+use gltf::animation::Interpolation;
+use itertools::Itertools;
+use nalgebra_glm as glm;
+
+use crate::Model;
+
+enum Keyframes {
+    Translation(Vec<glm::Vec3>),
+    Rotation(Vec<glm::Quat>),
+    Scale(Vec<glm::Vec3>),
+}
+
+struct Channel {
+    node: usize,
+    times: Vec<f32>,
+    step: bool,
+    keyframes: Keyframes,
+}
+
+impl Channel {
+    fn from(channel: &gltf::animation::Channel, buffers: &[Vec<u8>]) -> Option<Self> {
+        let reader = channel.reader(|buffer| Some(buffers[buffer.index()].as_slice()));
+        let times = reader.read_inputs()?.collect_vec();
+        let interpolation = channel.sampler().interpolation();
+        // Cubic spline keyframes are stored as in-tangent, value, out-tangent triples,
+        // only the values are kept and interpolated linearly
+        let stride = if interpolation == Interpolation::CubicSpline {
+            3
+        } else {
+            1
+        };
+        let offset = stride / 2;
+        let keyframes = match reader.read_outputs()? {
+            ReadOutputs::Translations(translations) => Keyframes::Translation(
+                translations
+                    .skip(offset)
+                    .step_by(stride)
+                    .map(|translation| glm::make_vec3(&translation))
+                    .collect_vec(),
+            ),
+            ReadOutputs::Rotations(rotations) => Keyframes::Rotation(
+                rotations
+                    .into_f32()
+                    .skip(offset)
+                    .step_by(stride)
+                    .map(|rotation| glm::make_quat(&rotation))
+                    .collect_vec(),
+            ),
+            ReadOutputs::Scales(scales) => Keyframes::Scale(
+                scales
+                    .skip(offset)
+                    .step_by(stride)
+                    .map(|scale| glm::make_vec3(&scale))
+                    .collect_vec(),
+            ),
+            ReadOutputs::MorphTargetWeights(_) => return None,
+        };
+        Some(Self {
+            node: channel.target().node().index(),
+            times,
+            step: interpolation == Interpolation::Step,
+            keyframes,
+        })
+    }
+
+    fn keyframes_at(&self, time: f32) -> (usize, usize, f32) {
+        let last = self.times.len() - 1;
+        match self
+            .times
+            .iter()
+            .position(|&keyframe_time| keyframe_time > time)
+        {
+            Some(0) => (0, 0, 0.0),
+            Some(next) if !self.step => {
+                let previous = next - 1;
+                let t = (time - self.times[previous]) / (self.times[next] - self.times[previous]);
+                (previous, next, t)
+            }
+            Some(next) => (next - 1, next - 1, 0.0),
+            None => (last, last, 0.0),
+        }
+    }
+
+    fn apply(&self, time: f32, model: &mut Model) {
+        let (previous, next, t) = self.keyframes_at(time);
+        match &self.keyframes {
+            Keyframes::Translation(translations) => {
+                model.set_translation(glm::lerp(&translations[previous], &translations[next], t))
+            }
+            Keyframes::Rotation(rotations) => {
+                model.set_rotation(slerp(&rotations[previous], &rotations[next], t))
+            }
+            Keyframes::Scale(scales) => {
+                model.set_scale(glm::lerp(&scales[previous], &scales[next], t))
+            }
+        }
+    }
+}
+
+fn slerp(from: &glm::Quat, to: &glm::Quat, t: f32) -> glm::Quat {
+    let dot = glm::quat_dot(from, to);
+    let to = if dot < 0.0 { -*to } else { *to };
+    // Nearly identical rotations make slerp numerically unstable, lerp is precise enough there
+    if dot.abs() > 0.9995 {
+        glm::quat_normalize(&glm::quat_lerp(from, &to, t))
+    } else {
+        glm::quat_slerp(from, &to, t)
+    }
+}
+
+pub struct Animation {
+    name: String,
+    duration: f32,
+    channels: Vec<Channel>,
+}
+
+impl Animation {
+    pub fn load_all(gltf: &gltf::Document, buffers: &[Vec<u8>]) -> Vec<Self> {
+        gltf.animations()
+            .map(|animation| Self::from(&animation, buffers))
+            .collect_vec()
+    }
+
+    pub fn from(animation: &gltf::Animation, buffers: &[Vec<u8>]) -> Self {
+        let channels = animation
+            .channels()
+            .filter_map(|channel| Channel::from(&channel, buffers))
+            .filter(|channel| !channel.times.is_empty())
+            .collect_vec();
+        let duration = channels
+            .iter()
+            .map(|channel| channel.times[channel.times.len() - 1])
+            .fold(0.0, f32::max);
+        Self {
+            name: animation.name().unwrap_or_default().to_string(),
+            duration,
+            channels,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn apply(&self, time: f32, model: &mut Model) {
+        for channel in &self.channels {
+            if let Some(node) = model.find_node_mut(channel.node) {
+                channel.apply(time, node);
+            }
+        }
+    }
+}
+
+pub struct Animator {
+    animations: Arc<Vec<Animation>>,
+    idle: Option<usize>,
+    playing: Option<usize>,
+    time: f32,
+}
+
+impl Animator {
+    pub fn new(animations: Arc<Vec<Animation>>, idle: Option<&str>) -> Self {
+        let mut animator = Self {
+            animations,
+            idle: None,
+            playing: None,
+            time: 0.0,
+        };
+        animator.idle = idle.and_then(|name| animator.find(name));
+        animator
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.animations
+            .iter()
+            .position(|animation| animation.name() == name)
+    }
+
+    // Plays the animation once, then goes back to looping the idle animation
+    pub fn play(&mut self, name: &str) {
+        if let Some(index) = self.find(name) {
+            self.playing = Some(index);
+            self.time = 0.0;
+        }
+    }
+
+    pub fn advance(&mut self, delta_time: f32, model: &mut Model) {
+        self.time += delta_time;
+        if let Some(playing) = self.playing {
+            if self.time > self.animations[playing].duration() {
+                self.playing = None;
+                self.time = 0.0;
+            }
+        }
+        if let Some(index) = self.playing.or(self.idle) {
+            let animation = &self.animations[index];
+            let time = if self.playing.is_some() || animation.duration() == 0.0 {
+                self.time
+            } else {
+                self.time % animation.duration()
+            };
+            animation.apply(time, model);
+        }
+    }
+}
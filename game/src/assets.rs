@@ -0,0 +1,206 @@
+use std::rc::Rc;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::animation::Animation;
+use crate::texture::Texture;
+use crate::{Color, Model};
+
+#[derive(Clone, Deserialize)]
+pub struct Manifest {
+    pub obstacle: MeshSource,
+    pub player: MeshSource,
+    pub wide_block: MeshSource,
+    pub moving_block: MeshSource,
+    pub shield: MeshSource,
+    pub slow_motion: MeshSource,
+    pub score_multiplier: MeshSource,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct MeshSource {
+    pub url: String,
+    pub node: Option<String>,
+    pub color: Option<Color>,
+    pub scale: Option<[f32; 3]>,
+    pub idle_animation: Option<String>,
+    pub dodge_animation: Option<String>,
+}
+
+impl Manifest {
+    pub async fn load(url: &str) -> Result<Self, JsValue> {
+        let data = fetch_bytes(url).await?;
+        Ok(serde_json::from_slice(&data).map_err(|error| error.to_string())?)
+    }
+
+    fn sources(&self) -> [&MeshSource; 7] {
+        [
+            &self.obstacle,
+            &self.player,
+            &self.wide_block,
+            &self.moving_block,
+            &self.shield,
+            &self.slow_motion,
+            &self.score_multiplier,
+        ]
+    }
+}
+
+// Every asset the manifest refers to, loaded once per URL
+// Cloning shares the loaded assets
+#[derive(Clone)]
+pub struct AssetLibrary {
+    urls: Rc<Vec<String>>,
+    assets: Rc<Vec<Asset>>,
+}
+
+impl AssetLibrary {
+    pub async fn load(manifest: &Manifest) -> Result<Self, JsValue> {
+        let mut urls = Vec::new();
+        let mut assets = Vec::new();
+        for source in manifest.sources() {
+            if !urls.contains(&source.url) {
+                assets.push(Asset::load(&source.url).await?);
+                urls.push(source.url.clone());
+            }
+        }
+        // Cubes are built from the manifest sources while playing, so a mesh that can't be drawn
+        // is turned down here instead
+        for source in manifest.sources() {
+            let index = urls.iter().position(|url| *url == source.url).unwrap();
+            assets[index].model(source)?;
+        }
+        Ok(Self {
+            urls: Rc::new(urls),
+            assets: Rc::new(assets),
+        })
+    }
+
+    pub fn index(&self, source: &MeshSource) -> usize {
+        self.urls
+            .iter()
+            .position(|url| *url == source.url)
+            .expect("Asset not loaded!")
+    }
+
+    pub fn get(&self, index: usize) -> &Asset {
+        &self.assets[index]
+    }
+}
+
+pub struct Asset {
+    document: gltf::Document,
+    buffers: Vec<Vec<u8>>,
+    textures: Vec<Arc<Texture>>,
+    // Parsed once here, every cube made from the asset plays the same ones
+    animations: Arc<Vec<Animation>>,
+}
+
+impl Asset {
+    pub async fn load(url: &str) -> Result<Self, JsValue> {
+        let data = fetch_bytes(url).await?;
+        let gltf::Gltf { document, mut blob } =
+            gltf::Gltf::from_slice(&data).map_err(|error| error.to_string())?;
+
+        let mut buffers = Vec::with_capacity(document.buffers().len());
+        for buffer in document.buffers() {
+            let mut data = match buffer.source() {
+                gltf::buffer::Source::Bin => blob.take().ok_or("Binary chunk missing")?,
+                gltf::buffer::Source::Uri(uri) => read_uri(url, uri).await?,
+            };
+            if data.len() < buffer.length() {
+                return Err(format!("Buffer {} of {} is too short", buffer.index(), url).into());
+            }
+            while data.len() % 4 != 0 {
+                data.push(0);
+            }
+            buffers.push(data);
+        }
+
+        let mut textures = Vec::with_capacity(document.images().len());
+        for image in document.images() {
+            let texture = match image.source() {
+                gltf::image::Source::View { view, .. } => {
+                    let buffer = &buffers[view.buffer().index()];
+                    Texture::decode(&buffer[view.offset()..(view.offset() + view.length())])?
+                }
+                gltf::image::Source::Uri { uri, .. } => {
+                    Texture::decode(&read_uri(url, uri).await?)?
+                }
+            };
+            textures.push(Arc::new(texture));
+        }
+
+        let animations = Arc::new(Animation::load_all(&document, &buffers));
+        Ok(Self {
+            document,
+            buffers,
+            textures,
+            animations,
+        })
+    }
+
+    pub fn buffers(&self) -> &[Vec<u8>] {
+        &self.buffers
+    }
+
+    pub fn animations(&self) -> &Arc<Vec<Animation>> {
+        &self.animations
+    }
+
+    fn node(&self, name: Option<&str>) -> Option<gltf::Node<'_>> {
+        match name {
+            Some(name) => self.document.nodes().find(|node| node.name() == Some(name)),
+            None => self.document.default_scene()?.nodes().nth(0),
+        }
+    }
+
+    pub fn model(&self, source: &MeshSource) -> Result<Model, JsValue> {
+        let node = self
+            .node(source.node.as_deref())
+            .ok_or(format!("Mesh node of {} not found", source.url))?;
+        Model::from_node(&node, &self.buffers, &self.textures, source.color)
+    }
+}
+
+async fn read_uri(base: &str, uri: &str) -> Result<Vec<u8>, JsValue> {
+    if let Some(data_uri) = uri.strip_prefix("data:") {
+        let (_, encoded) = data_uri
+            .split_once(";base64,")
+            .ok_or("Unsupported data URI")?;
+        Ok(base64::decode(encoded).map_err(|error| error.to_string())?)
+    } else {
+        fetch_bytes(&resolve_url(base, uri)?).await
+    }
+}
+
+fn resolve_url(base: &str, relative: &str) -> Result<String, JsValue> {
+    let location = web_sys::window()
+        .ok_or("Couldn't get window")?
+        .location()
+        .href()?;
+    let base = web_sys::Url::new_with_base(base, &location)?;
+    Ok(web_sys::Url::new_with_base(relative, &base.href())?.href())
+}
+
+// Also used by the benchmark worker, which has no window
+async fn fetch_bytes(url: &str) -> Result<Vec<u8>, JsValue> {
+    let request = match web_sys::window() {
+        Some(window) => window.fetch_with_str(url),
+        None => js_sys::global()
+            .unchecked_into::<web_sys::WorkerGlobalScope>()
+            .fetch_with_str(url),
+    };
+    let response = JsFuture::from(request)
+        .await?
+        .dyn_into::<web_sys::Response>()?;
+    if !response.ok() {
+        return Err(format!("Couldn't fetch {}: {}", url, response.status()).into());
+    }
+    let data = JsFuture::from(response.array_buffer()?).await?;
+    Ok(js_sys::Uint8Array::new(&data).to_vec())
+}
@@ -0,0 +1,81 @@
+use instant::Instant;
+use wasm_bindgen::prelude::*;
+
+use crate::assets::{Asset, Manifest};
+use crate::color::Color;
+use crate::cube::Cube;
+use crate::{gfx, glm, MANIFEST_URL};
+
+const GRID_SIZE: usize = 12;
+
+fn average_frame_time(
+    frames: u32,
+    framebuffer: &mut gfx::Framebuffer,
+    mut draw_frame: impl FnMut(&mut gfx::Framebuffer),
+) -> f64 {
+    let start = Instant::now();
+    for _ in 0..frames {
+        framebuffer.clear(Color::BLACK);
+        draw_frame(framebuffer);
+    }
+    start.elapsed().as_secs_f64() * 1000.0 / frames.max(1) as f64
+}
+
+// Draws a grid of obstacles and reports the average frame time of each rasterization path, runs
+// in a worker so the tiled path can use the thread pool
+#[wasm_bindgen]
+pub async fn benchmark(frames: u32, width: usize, height: usize) -> Result<String, JsValue> {
+    let manifest = Manifest::load(MANIFEST_URL).await?;
+    let asset = Asset::load(&manifest.obstacle.url).await?;
+    asset.model(&manifest.obstacle)?;
+    let mut cubes = Vec::with_capacity(GRID_SIZE * GRID_SIZE);
+    for row in 0..GRID_SIZE {
+        for column in 0..GRID_SIZE {
+            let mut cube = Cube::new(&asset, &manifest.obstacle);
+            cube.move_to(
+                (column as f32 - GRID_SIZE as f32 / 2.0) * 1.5,
+                row as f32 * 1.5,
+            );
+            cubes.push(cube);
+        }
+    }
+
+    let view = glm::look_at(
+        &glm::vec3(0.0, 6.0, 8.0),
+        &glm::vec3(0.0, 0.0, -8.0),
+        &glm::vec3(0.0, 1.0, 0.0),
+    );
+    let projection =
+        glm::perspective_fov_zo(60_f32.to_radians(), width as f32, height as f32, 0.1, 100.0);
+    let camera = projection * view;
+    let draw_cubes = |framebuffer: &mut gfx::Framebuffer| {
+        for cube in &cubes {
+            cube.draw(framebuffer, &camera, asset.buffers());
+        }
+    };
+
+    let mut framebuffer = gfx::Framebuffer::new(width, height);
+    let single_threaded = average_frame_time(frames, &mut framebuffer, |framebuffer| {
+        draw_cubes(framebuffer);
+        framebuffer.rasterize();
+    });
+    let tiled = average_frame_time(frames, &mut framebuffer, |framebuffer| {
+        draw_cubes(framebuffer);
+        framebuffer.rasterize_tiled();
+    });
+
+    Ok(format!(
+        "{}x{}, {} obstacles, {} frames\nsingle-threaded: {:.2} ms/frame\ntiled{}: {:.2} ms/frame",
+        width,
+        height,
+        cubes.len(),
+        frames,
+        single_threaded,
+        if gfx::parallel_rasterization_enabled() {
+            " (parallel)"
+        } else {
+            ""
+        },
+        tiled
+    ))
+}
@@ -0,0 +1,118 @@
+use crate::glm;
+
+const SHAKE_DECAY: f32 = 1.5;
+const SHAKE_MAX_OFFSET: f32 = 0.15;
+const FOV_LERP: f32 = 2.0;
+const FOLLOW_LERP: f32 = 4.0;
+// Room left on the sides of the framed area
+const FRAMING_MARGIN: f32 = 1.15;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum CameraMode {
+    Fixed,
+    Follow,
+}
+
+pub struct Camera {
+    mode: CameraMode,
+    position: glm::Vec3,
+    target: glm::Vec3,
+    // Where the camera sits relative to the followed point in follow mode
+    follow_x: f32,
+    // Half of the width that has to stay in view around the target, 0 when nothing is framed
+    framed_half_width: f32,
+    fov: f32,
+    target_fov: f32,
+    near: f32,
+    far: f32,
+    aspect: f32,
+    shake: f32,
+    time: f32,
+}
+
+impl Camera {
+    pub fn new(position: glm::Vec3, target: glm::Vec3, fov: f32) -> Self {
+        Self {
+            mode: CameraMode::Fixed,
+            position,
+            target,
+            follow_x: 0.0,
+            framed_half_width: 0.0,
+            fov,
+            target_fov: fov,
+            near: 0.1,
+            far: 100.0,
+            aspect: 1.0,
+            shake: 0.0,
+            time: 0.0,
+        }
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        self.mode = mode;
+    }
+
+    pub fn set_aspect(&mut self, width: f32, height: f32) {
+        if width > 0.0 && height > 0.0 {
+            self.aspect = width / height;
+        }
+    }
+
+    // The camera backs away from the target when the area doesn't fit on narrow screens
+    pub fn set_framed_half_width(&mut self, half_width: f32) {
+        self.framed_half_width = half_width;
+    }
+
+    // The FOV eases towards this value instead of jumping to it
+    pub fn set_target_fov(&mut self, fov: f32) {
+        self.target_fov = fov;
+    }
+
+    // Strength from 0 to 1, shakes add up and fade out over time
+    pub fn shake(&mut self, strength: f32) {
+        self.shake = (self.shake + strength).min(1.0);
+    }
+
+    pub fn advance(&mut self, delta_time: f32, followed_x: f32) {
+        self.time += delta_time;
+        self.shake = (self.shake - SHAKE_DECAY * delta_time).max(0.0);
+        self.fov += (self.target_fov - self.fov) * (FOV_LERP * delta_time).min(1.0);
+        let follow_x = match self.mode {
+            CameraMode::Fixed => 0.0,
+            CameraMode::Follow => followed_x,
+        };
+        self.follow_x += (follow_x - self.follow_x) * (FOLLOW_LERP * delta_time).min(1.0);
+    }
+
+    pub fn view(&self) -> glm::Mat4 {
+        // Squaring makes weak shakes subtle and strong ones violent
+        let strength = self.shake * self.shake * SHAKE_MAX_OFFSET;
+        let shake = glm::vec3(
+            (self.time * 47.0).sin() + (self.time * 31.0).cos() * 0.5,
+            (self.time * 53.0).cos() + (self.time * 29.0).sin() * 0.5,
+            0.0,
+        ) * strength;
+        let follow = glm::vec3(self.follow_x, 0.0, 0.0);
+        let offset = self.position - self.target;
+        let horizontal_tan = (self.fov.to_radians() / 2.0).tan() * self.aspect;
+        let framing_distance = self.framed_half_width * FRAMING_MARGIN / horizontal_tan;
+        let position = self.target + offset * (framing_distance / offset.norm()).max(1.0);
+        glm::look_at(
+            &(position + follow + shake),
+            &(self.target + follow + shake * 0.5),
+            &glm::vec3(0.0, 1.0, 0.0),
+        )
+    }
+
+    pub fn projection(&self) -> glm::Mat4 {
+        glm::perspective_zo(self.aspect, self.fov.to_radians(), self.near, self.far)
+    }
+
+    pub fn view_projection(&self) -> glm::Mat4 {
+        self.projection() * self.view()
+    }
+}
@@ -0,0 +1,96 @@
+use crate::glm;
+
+// Axis aligned bounding box
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Aabb {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_bounds(bounds: &gltf::mesh::BoundingBox) -> Self {
+        Self::new(glm::make_vec3(&bounds.min), glm::make_vec3(&bounds.max))
+    }
+
+    pub fn center(&self) -> glm::Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn half_extents(&self) -> glm::Vec3 {
+        (self.max - self.min) / 2.0
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            glm::min2(&self.min, &other.min),
+            glm::max2(&self.max, &other.max),
+        )
+    }
+
+    pub fn translated(&self, offset: &glm::Vec3) -> Aabb {
+        Aabb::new(self.min + offset, self.max + offset)
+    }
+
+    // Box around all 8 transformed corners, so rotations only ever grow it
+    pub fn transformed(&self, transform: &glm::Mat4) -> Aabb {
+        let mut min = glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = -min;
+        for corner in 0..8 {
+            let pick = |axis: usize| {
+                if corner & (1 << axis) == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                }
+            };
+            let point = glm::vec3(pick(0), pick(1), pick(2));
+            let point = (transform * point.push(1.0)).xyz();
+            min = glm::min2(&min, &point);
+            max = glm::max2(&max, &point);
+        }
+        Aabb::new(min, max)
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] < other.max[axis] && other.min[axis] < self.max[axis])
+    }
+
+    // Space between the boxes along the x axis, negative when they overlap
+    pub fn gap_x(&self, other: &Aabb) -> f32 {
+        self.min.x.max(other.min.x) - self.max.x.min(other.max.x)
+    }
+
+    // Whether this box touches the other one at any point while moving by displacement relative to
+    // it, the other box is grown by this one's size and hit with a ray from this box's centre
+    pub fn sweep_intersects(&self, other: &Aabb, displacement: &glm::Vec3) -> bool {
+        let half_extents = self.half_extents();
+        let expanded = Aabb::new(other.min - half_extents, other.max + half_extents);
+        let origin = self.center();
+        let mut entry: f32 = 0.0;
+        let mut exit: f32 = 1.0;
+        for axis in 0..3 {
+            if displacement[axis] == 0.0 {
+                if origin[axis] <= expanded.min[axis] || origin[axis] >= expanded.max[axis] {
+                    return false;
+                }
+                continue;
+            }
+            let inverse = 1.0 / displacement[axis];
+            let mut near = (expanded.min[axis] - origin[axis]) * inverse;
+            let mut far = (expanded.max[axis] - origin[axis]) * inverse;
+            if near > far {
+                std::mem::swap(&mut near, &mut far);
+            }
+            entry = entry.max(near);
+            exit = exit.min(far);
+            if entry >= exit {
+                return false;
+            }
+        }
+        true
+    }
+}
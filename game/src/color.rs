@@ -1,4 +1,6 @@
-#[derive(Copy, Clone, Default, bytemuck::Zeroable, bytemuck::Pod)]
+#[derive(
+    Copy, Clone, Default, PartialEq, bytemuck::Zeroable, bytemuck::Pod, serde::Deserialize,
+)]
 #[repr(C)]
 pub struct Color(u8, u8, u8);
 
@@ -166,4 +168,38 @@ impl Color {
     pub const SILVER: Self = Self(192, 192, 192);
     pub const LIGHT_GRAY: Self = Self(211, 211, 211);
     pub const GAINSBORO: Self = Self(220, 220, 220);
+
+    // glTF materials and vertex colours are linear, the framebuffer is sRGB
+    pub fn from_linear(rgb: [f32; 3]) -> Self {
+        let encode = |channel: f32| {
+            let channel = channel.clamp(0.0, 1.0);
+            let srgb = if channel <= 0.0031308 {
+                channel * 12.92
+            } else {
+                1.055 * channel.powf(1.0 / 2.4) - 0.055
+            };
+            (srgb * 255.0).round() as u8
+        };
+        Self(encode(rgb[0]), encode(rgb[1]), encode(rgb[2]))
+    }
+
+    // sRGB is close enough to a power curve that multiplying encoded channels matches multiplying
+    // linear ones
+    pub fn modulate(self, other: Self) -> Self {
+        let multiply = |a: u8, b: u8| ((a as u16 * b as u16 + 127) / 255) as u8;
+        Self(
+            multiply(self.0, other.0),
+            multiply(self.1, other.1),
+            multiply(self.2, other.2),
+        )
+    }
+
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+        Self(
+            mix(self.0, other.0),
+            mix(self.1, other.1),
+            mix(self.2, other.2),
+        )
+    }
 }
@@ -1,22 +1,36 @@
-use crate::{gfx, glm, Color, Model};
+use crate::animation::Animator;
+use crate::assets::MeshSource;
+use crate::collision::Aabb;
+use crate::{gfx, glm, Asset, Color, Model};
 
 pub struct Cube {
     model: Model,
+    animator: Animator,
     x: f32,
     y: f32,
+    // Where the cube was at the start of the step, collisions are swept from there
+    previous_x: f32,
+    previous_y: f32,
+    scale: glm::Vec3,
+    color: Option<Color>,
 }
 
 impl Cube {
-    pub fn new(gltf: &gltf::Document, buffer: &[u8], color: Color) -> Self {
-        let cube_node = gltf
-            .nodes()
-            .find(|node| node.name() == Some("Cube"))
-            .unwrap();
-        let model = Model::from_node(&cube_node, buffer, color);
+    pub fn new(asset: &Asset, source: &MeshSource) -> Self {
+        let model = asset
+            .model(source)
+            .expect("Mesh checked when the assets loaded!");
         Cube {
             model,
+            animator: Animator::new(asset.animations().clone(), source.idle_animation.as_deref()),
             x: 0.0,
             y: 0.0,
+            previous_x: 0.0,
+            previous_y: 0.0,
+            scale: source
+                .scale
+                .map_or(glm::vec3(1.0, 1.0, 1.0), |scale| glm::make_vec3(&scale)),
+            color: source.color,
         }
     }
 
@@ -28,17 +42,77 @@ impl Cube {
         self.y
     }
 
+    // Also resets the previous position, the cube teleports instead of sweeping there
     pub fn move_to(&mut self, x: f32, y: f32) {
         self.x = x;
         self.y = y;
-        self.model.set_translation(glm::vec3(self.x, 0.0, -self.y));
+        self.previous_x = x;
+        self.previous_y = y;
     }
 
+    pub fn begin_step(&mut self) {
+        self.previous_x = self.x;
+        self.previous_y = self.y;
+    }
+
+    pub fn step_to(&mut self, x: f32, y: f32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    pub fn play_animation(&mut self, name: &str) {
+        self.animator.play(name);
+    }
+
+    pub fn advance_animation(&mut self, delta_time: f32) {
+        self.animator.advance(delta_time, &mut self.model);
+    }
+
+    pub fn set_width(&mut self, width: f32) {
+        self.scale[0] = width;
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        self.model.set_color(color);
+    }
+
+    // Back to the manifest colour, or the model's own colours when there is none
+    pub fn reset_color(&mut self) {
+        match self.color {
+            Some(color) => self.model.set_color(color),
+            None => self.model.reset_color(),
+        }
+    }
+
+    fn transform(&self) -> glm::Mat4 {
+        glm::translation(&glm::vec3(self.x, 0.0, -self.y)) * glm::scaling(&self.scale)
+    }
+
+    // World space bounds at the current position
+    pub fn bounds(&self) -> Aabb {
+        self.model.bounds().transformed(&self.transform())
+    }
+
+    fn step_displacement(&self) -> glm::Vec3 {
+        glm::vec3(self.x - self.previous_x, 0.0, self.previous_y - self.y)
+    }
+
+    // Swept over the whole step, so a fast cube can't pass through another one between frames
     pub fn collides_with(&self, other: &Cube) -> bool {
-        (self.x - other.x).abs() < 1.0 && (self.y - other.y).abs() < 1.0
+        let displacement = self.step_displacement();
+        let other_displacement = other.step_displacement();
+        let start = self.bounds().translated(&-displacement);
+        let other_start = other.bounds().translated(&-other_displacement);
+        start.sweep_intersects(&other_start, &(displacement - other_displacement))
     }
 
-    pub fn draw(&self, framebuffer: &mut gfx::Framebuffer, camera: &glm::Mat4, buffer: &[u8]) {
-        self.model.draw(framebuffer, camera, buffer);
+    pub fn draw(
+        &self,
+        framebuffer: &mut gfx::Framebuffer,
+        camera: &glm::Mat4,
+        buffers: &[Vec<u8>],
+    ) {
+        self.model
+            .draw(framebuffer, &(camera * self.transform()), buffers);
     }
 }
@@ -1,99 +1,634 @@
-use crate::{gfx, glm, Color, Cube};
+use crate::assets::{AssetLibrary, MeshSource};
+use crate::replay::{GameInput, Replay};
+use crate::rules::{GameRules, Lanes};
+use crate::{gfx, glm, Camera, Color, Cube, Manifest};
 use rand::prelude::*;
+use rand::rngs::StdRng;
+use serde::Serialize;
 use std::time::Duration;
 
-const X_POSITIONS: [f32; 3] = [-1.1, 0.0, 1.1];
+// Meshes span -0.5..0.5, so an unscaled block exactly fills one lane
+const BLOCK_WIDTH: f32 = 1.0;
+const BASE_FOV: f32 = 45.0;
+const MAX_FOV: f32 = 70.0;
+const RESUME_COUNTDOWN: f32 = 3.0;
+// Longer frames are slowed down instead of letting cubes jump through the player
+const MAX_DELTA_TIME: Duration = Duration::from_millis(50);
+// The simulation always advances in steps of the same length so replays play back exactly
+const STEP: Duration = Duration::from_micros(1_000_000 / 60);
+// Gap between the player and a passing hazard that still counts as a near miss
+const NEAR_MISS_GAP: f32 = 0.5;
+const SLOW_MOTION_DURATION: f32 = 5.0;
+const SLOW_MOTION_FACTOR: f32 = 0.5;
+const SCORE_MULTIPLIER_DURATION: f32 = 10.0;
+const SCORE_MULTIPLIER: u32 = 2;
+// Moving blocks switch lanes once they are this close to the player
+const LANE_CHANGE_DISTANCE: f32 = 12.0;
+const LANE_CHANGE_RATE: f32 = 2.5;
+const GHOST_COLOR: Color = Color::GHOST_WHITE;
+const GHOST_OPACITY: f32 = 0.35;
+// Set by build.sh so runs can be told apart by the exact build that played them
+const GAME_VERSION: &str = match option_env!("GAME_BUILD") {
+    Some(build) => build,
+    None => env!("CARGO_PKG_VERSION"),
+};
+
+// Submitted along with the score
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunStats {
+    // Seconds of play, pauses don't count
+    pub duration: f32,
+    pub obstacles_passed: u32,
+    pub max_speed: f32,
+    // The full ruleset as JSON, presets may change between versions
+    pub rules: String,
+    pub version: &'static str,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum PowerUp {
+    Shield,
+    SlowMotion,
+    ScoreMultiplier,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum EntityKind {
+    Block,
+    // Spans its lane and the one to the right of it
+    WideBlock,
+    MovingBlock { lane_changed: bool },
+    PowerUp(PowerUp),
+}
+
+impl EntityKind {
+    fn random(rng: &mut StdRng) -> Self {
+        match rng.gen_range(0..100) {
+            0..=59 => EntityKind::Block,
+            60..=74 => EntityKind::WideBlock,
+            75..=89 => EntityKind::MovingBlock {
+                lane_changed: false,
+            },
+            _ => EntityKind::PowerUp(
+                *[
+                    PowerUp::Shield,
+                    PowerUp::SlowMotion,
+                    PowerUp::ScoreMultiplier,
+                ]
+                .choose(rng)
+                .unwrap(),
+            ),
+        }
+    }
+
+    fn is_hazard(self) -> bool {
+        !matches!(self, EntityKind::PowerUp(_))
+    }
+
+    fn source(self, manifest: &Manifest) -> &MeshSource {
+        match self {
+            EntityKind::Block => &manifest.obstacle,
+            EntityKind::WideBlock => &manifest.wide_block,
+            EntityKind::MovingBlock { .. } => &manifest.moving_block,
+            EntityKind::PowerUp(PowerUp::Shield) => &manifest.shield,
+            EntityKind::PowerUp(PowerUp::SlowMotion) => &manifest.slow_motion,
+            EntityKind::PowerUp(PowerUp::ScoreMultiplier) => &manifest.score_multiplier,
+        }
+    }
+
+    fn lane_x(self, lane: usize, lanes: &Lanes) -> f32 {
+        match self {
+            EntityKind::WideBlock => (lanes.x(lane) + lanes.x(lane + 1)) / 2.0,
+            _ => lanes.x(lane),
+        }
+    }
+}
+
+// Anything travelling towards the player, the collision shape comes from the cube's scale
+struct Entity {
+    kind: EntityKind,
+    cube: Cube,
+    asset: usize,
+    lane: usize,
+}
+
+impl Entity {
+    fn spawn(
+        manifest: &Manifest,
+        assets: &AssetLibrary,
+        lanes: &Lanes,
+        rng: &mut StdRng,
+        y: f32,
+    ) -> Self {
+        let mut kind = EntityKind::random(rng);
+        if kind == EntityKind::WideBlock && lanes.count() < 2 {
+            kind = EntityKind::Block;
+        }
+        let lane_count = match kind {
+            EntityKind::WideBlock => lanes.count() - 1,
+            _ => lanes.count(),
+        };
+        let lane = rng.gen_range(0..lane_count);
+        let source = kind.source(manifest);
+        let asset = assets.index(source);
+        let mut cube = Cube::new(assets.get(asset), source);
+        if kind == EntityKind::WideBlock {
+            cube.set_width(lanes.spacing() + BLOCK_WIDTH);
+        }
+        cube.move_to(kind.lane_x(lane, lanes), y);
+        Self {
+            kind,
+            cube,
+            asset,
+            lane,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Running,
+    Paused,
+    Resuming { remaining: f32 },
+    Over,
+}
 
 pub struct Game {
     player: Cube,
-    player_target_x: i8,
-    cubes: Vec<Cube>,
+    player_lane: usize,
+    player_dodge_animation: Option<String>,
+    entities: Vec<Entity>,
     speed: f32,
+    max_speed: f32,
+    obstacles_passed: u32,
     spawn_y: f32,
     score: u32,
-    score_indicator: web_sys::HtmlSpanElement,
-    buffer: Vec<u8>,
-    rng: ThreadRng,
+    // Ghosts are simulated without one
+    score_indicator: Option<web_sys::HtmlSpanElement>,
+    rules: GameRules,
+    lanes: Lanes,
+    manifest: Manifest,
+    assets: AssetLibrary,
+    player_asset: usize,
+    shield: bool,
+    slow_motion: f32,
+    score_multiplier: f32,
+    rng: StdRng,
+    camera: Camera,
+    state: State,
+    accumulated_time: Duration,
+    // Simulation steps played while running, replay inputs are tied to these
+    frame: u32,
+    replay: Replay,
+    // Inputs of a replay being watched and how many of them were applied
+    playback: Option<(Vec<(u32, GameInput)>, usize)>,
+    // Another run simulated alongside this one, only its player is drawn
+    ghost: Option<Box<Game>>,
 }
 
 impl Game {
-    pub fn start(n_cubes: usize, score_indicator: web_sys::HtmlSpanElement) -> Self {
-        let (gltf, mut buffers, _) =
-            gltf::import_slice(include_bytes!("../assets/cube.gltf")).unwrap();
-        let buffer = buffers.swap_remove(0).to_vec();
-        let mut cubes = Vec::with_capacity(n_cubes);
+    // The seed comes from the server's run ticket so submitted runs can be checked against it
+    pub fn start(
+        rules: GameRules,
+        seed: u32,
+        manifest: Manifest,
+        assets: AssetLibrary,
+        score_indicator: web_sys::HtmlSpanElement,
+    ) -> Self {
+        Self::new(
+            Replay::new(seed, rules),
+            None,
+            manifest,
+            assets,
+            Some(score_indicator),
+        )
+    }
 
-        let mut rng = thread_rng();
+    // Plays a recorded run back, player input is ignored
+    pub fn watch(
+        replay: Replay,
+        manifest: Manifest,
+        assets: AssetLibrary,
+        score_indicator: web_sys::HtmlSpanElement,
+    ) -> Self {
+        let events = replay.events.clone();
+        let replay = Replay::new(replay.seed, replay.rules);
+        Self::new(
+            replay,
+            Some((events, 0)),
+            manifest,
+            assets,
+            Some(score_indicator),
+        )
+    }
+
+    fn new(
+        replay: Replay,
+        playback: Option<(Vec<(u32, GameInput)>, usize)>,
+        manifest: Manifest,
+        assets: AssetLibrary,
+        score_indicator: Option<web_sys::HtmlSpanElement>,
+    ) -> Self {
+        let rules = replay.rules.clone();
+        let mut entities = Vec::with_capacity(rules.obstacle_count);
+
+        let lanes = rules.lanes();
+        let mut rng = StdRng::seed_from_u64(replay.seed as u64);
         let mut y = 6.0;
-        for _ in 0..n_cubes {
-            let mut cube = Cube::new(&gltf, &buffer, Color::CYAN);
-            cube.move_to(*X_POSITIONS.choose(&mut rng).unwrap() as f32, y);
-            cubes.push(cube);
-            y += rng.gen_range(6.0..10.0);
+        for _ in 0..rules.obstacle_count {
+            entities.push(Entity::spawn(&manifest, &assets, &lanes, &mut rng, y));
+            y += rng.gen_range(rules.spawn_spacing.clone());
         }
 
+        let player_asset = assets.index(&manifest.player);
+        let mut player = Cube::new(assets.get(player_asset), &manifest.player);
+        player.move_to(lanes.x(lanes.middle()), 0.0);
+        let mut camera = Camera::new(glm::vec3(0.0, 2.0, 4.0), glm::vec3(0.0, 0.5, 0.0), BASE_FOV);
+        camera.set_framed_half_width(lanes.half_width(BLOCK_WIDTH));
         Self {
-            player: Cube::new(&gltf, &buffer, Color::MAGENTA),
-            player_target_x: 1,
-            cubes,
-            speed: 2.0,
+            player,
+            player_lane: lanes.middle(),
+            player_dodge_animation: manifest.player.dodge_animation.clone(),
+            entities,
+            speed: rules.start_speed,
+            max_speed: rules.start_speed,
+            obstacles_passed: 0,
             spawn_y: y,
             score: 0,
             score_indicator,
-            buffer,
+            rules,
+            lanes,
+            manifest,
+            assets,
+            player_asset,
+            shield: false,
+            slow_motion: 0.0,
+            score_multiplier: 0.0,
             rng,
+            camera,
+            state: State::Running,
+            accumulated_time: Duration::ZERO,
+            frame: 0,
+            replay,
+            playback,
+            ghost: None,
         }
     }
 
-    pub fn draw(&self, framebuffer: &mut gfx::Framebuffer, camera: &glm::Mat4) {
-        for cube in &self.cubes {
-            cube.draw(framebuffer, camera, &self.buffer);
+    // Races a recorded run, which is only fair when both play the same world
+    pub fn race(&mut self, replay: Replay) -> Result<(), String> {
+        if replay.seed != self.replay.seed || replay.rules != self.rules {
+            return Err("The ghost was recorded with a different seed or rules".to_string());
         }
-        self.player.draw(framebuffer, camera, &self.buffer);
+        let events = replay.events.clone();
+        let replay = Replay::new(replay.seed, replay.rules);
+        let mut ghost = Self::new(
+            replay,
+            Some((events, 0)),
+            self.manifest.clone(),
+            self.assets.clone(),
+            None,
+        );
+        ghost.player.set_color(GHOST_COLOR);
+        self.ghost = Some(Box::new(ghost));
+        Ok(())
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    // The run recorded so far, finished once the player crashes
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn rules(&self) -> &GameRules {
+        &self.rules
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
     }
 
-    pub fn advance(&mut self, delta_time: &Duration) {
-        for cube in &mut self.cubes {
-            cube.move_to(
-                cube.x(),
-                cube.y() - self.speed * delta_time.as_secs_f32() * 1.5,
+    pub fn draw(&self, framebuffer: &mut gfx::Framebuffer) {
+        let camera = &self.camera.view_projection();
+        for entity in &self.entities {
+            entity
+                .cube
+                .draw(framebuffer, camera, self.assets.get(entity.asset).buffers());
+        }
+        self.player.draw(
+            framebuffer,
+            camera,
+            self.assets.get(self.player_asset).buffers(),
+        );
+        if let Some(ghost) = self
+            .ghost
+            .as_ref()
+            .filter(|ghost| ghost.state != State::Over)
+        {
+            framebuffer.set_line_opacity(GHOST_OPACITY);
+            ghost.player.draw(
+                framebuffer,
+                camera,
+                ghost.assets.get(ghost.player_asset).buffers(),
             );
+            framebuffer.set_line_opacity(1.0);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state != State::Running
+    }
+
+    pub fn pause(&mut self) {
+        if self.state != State::Over {
+            self.state = State::Paused;
+        }
+    }
+
+    // Play continues after a countdown so the player has time to get ready
+    pub fn resume(&mut self) {
+        if self.state == State::Paused {
+            self.state = State::Resuming {
+                remaining: RESUME_COUNTDOWN,
+            };
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        match self.state {
+            State::Running => self.pause(),
+            State::Paused => self.resume(),
+            State::Resuming { .. } | State::Over => (),
+        }
+    }
+
+    // Text for the pause overlay, None while running
+    pub fn pause_text(&self) -> Option<String> {
+        match self.state {
+            State::Running | State::Over => None,
+            State::Paused => Some("PAUSED".to_string()),
+            State::Resuming { remaining } => Some((remaining.ceil() as u32).to_string()),
+        }
+    }
+
+    // Runs as many steps as fit in the elapsed time, returns true when the player crashed
+    pub fn advance(&mut self, delta_time: &Duration) -> bool {
+        self.accumulated_time += (*delta_time).min(MAX_DELTA_TIME);
+        while self.accumulated_time >= STEP {
+            self.accumulated_time -= STEP;
+            if self.tick() {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn tick(&mut self) -> bool {
+        self.step();
+        if self.check_collision() {
+            self.state = State::Over;
+            self.replay.finish(self.frame, self.score);
+            return true;
+        }
+        false
+    }
+
+    fn step(&mut self) {
+        let delta_time = &STEP;
+        // Nothing moves while paused, so collision checks see no motion either
+        self.player.begin_step();
+        for entity in &mut self.entities {
+            entity.cube.begin_step();
+        }
+        match self.state {
+            State::Running => (),
+            State::Paused | State::Over => return,
+            State::Resuming { remaining } => {
+                let remaining = remaining - delta_time.as_secs_f32();
+                self.state = if remaining <= 0.0 {
+                    State::Running
+                } else {
+                    State::Resuming { remaining }
+                };
+                return;
+            }
+        }
+        if let Some(ghost) = &mut self.ghost {
+            if ghost.state != State::Over {
+                ghost.tick();
+                // Picking up a shield recolours the player
+                ghost.player.set_color(GHOST_COLOR);
+            }
+        }
+        if let Some((events, applied)) = &mut self.playback {
+            let mut inputs = Vec::new();
+            while let Some(&(frame, input)) = events.get(*applied) {
+                if frame > self.frame {
+                    break;
+                }
+                inputs.push(input);
+                *applied += 1;
+            }
+            for input in inputs {
+                self.apply_input(input);
+            }
+        }
+        self.frame += 1;
+        let delta_seconds = delta_time.as_secs_f32();
+        let power_ups_active = self.slow_motion > 0.0 || self.score_multiplier > 0.0;
+        self.slow_motion = (self.slow_motion - delta_seconds).max(0.0);
+        self.score_multiplier = (self.score_multiplier - delta_seconds).max(0.0);
+        if power_ups_active {
+            self.update_score_indicator();
+        }
+        // Slow motion only slows the world down, the player keeps moving at full speed
+        let world_delta = if self.slow_motion > 0.0 {
+            delta_seconds * SLOW_MOTION_FACTOR
+        } else {
+            delta_seconds
+        };
+
+        let player_bounds = self.player.bounds();
+        let mut passed = Vec::new();
+        for (index, entity) in self.entities.iter_mut().enumerate() {
+            let cube = &mut entity.cube;
+            cube.advance_animation(delta_seconds);
+            if let EntityKind::MovingBlock {
+                lane_changed: false,
+            } = entity.kind
+            {
+                // With a single lane there's nowhere to move to
+                if cube.y() < LANE_CHANGE_DISTANCE && self.lanes.count() >= 2 {
+                    entity.lane = match entity.lane {
+                        0 => 1,
+                        lane if lane == self.lanes.count() - 1 => lane - 1,
+                        lane => *[lane - 1, lane + 1].choose(&mut self.rng).unwrap(),
+                    };
+                    entity.kind = EntityKind::MovingBlock { lane_changed: true };
+                }
+            }
+            let x = cube.x()
+                + (entity.kind.lane_x(entity.lane, &self.lanes) - cube.x())
+                    * (LANE_CHANGE_RATE * world_delta).min(1.0);
+            let previous_y = cube.y();
+            cube.step_to(x, cube.y() - self.speed * world_delta * 1.5);
+            let gap = cube.bounds().gap_x(&player_bounds);
+            if entity.kind.is_hazard()
+                && previous_y > self.player.y()
+                && cube.y() <= self.player.y()
+                && (0.0..NEAR_MISS_GAP).contains(&gap)
+            {
+                self.camera.shake(0.4);
+            }
             if cube.y() < -3.0 {
-                cube.move_to(*X_POSITIONS.choose(&mut self.rng).unwrap(), self.spawn_y);
-                self.score += 1;
-                self.score_indicator.set_inner_text(&self.score.to_string());
-                self.speed += 0.1;
+                passed.push(index);
+            }
+        }
+        for index in passed {
+            if self.entities[index].kind.is_hazard() {
+                self.score += if self.score_multiplier > 0.0 {
+                    SCORE_MULTIPLIER
+                } else {
+                    1
+                };
+                self.update_score_indicator();
+                self.obstacles_passed += 1;
+                self.speed = (self.speed + self.rules.speed_increase).min(self.rules.max_speed);
+                self.max_speed = self.max_speed.max(self.speed);
             }
+            self.respawn(index);
         }
         let new_player_x = self.player.x()
-            + (X_POSITIONS[self.player_target_x as usize] - self.player.x())
+            + (self.lanes.x(self.player_lane) - self.player.x())
                 * delta_time.as_secs_f32()
-                * 5.0;
-        self.player.move_to(new_player_x, self.player.y());
+                * self.rules.player_lerp_rate;
+        self.player.step_to(new_player_x, self.player.y());
+        self.player.advance_animation(delta_time.as_secs_f32());
+
+        self.camera
+            .set_target_fov((BASE_FOV + (self.speed - self.rules.start_speed) * 2.0).min(MAX_FOV));
+        self.camera
+            .advance(delta_time.as_secs_f32(), self.player.x());
     }
 
-    pub fn move_left(&mut self) {
-        self.player_target_x = (self.player_target_x - 1).rem_euclid(3);
+    // Inputs only count while running and are recorded for the replay
+    pub fn input(&mut self, input: GameInput) {
+        if self.state != State::Running || self.is_replay() {
+            return;
+        }
+        self.replay.record(self.frame, input);
+        self.apply_input(input);
     }
 
-    pub fn move_right(&mut self) {
-        self.player_target_x = (self.player_target_x + 1).rem_euclid(3);
+    fn apply_input(&mut self, input: GameInput) {
+        match input {
+            GameInput::Left => self.move_to(self.lanes.offset(self.player_lane, -1)),
+            GameInput::Right => self.move_to(self.lanes.offset(self.player_lane, 1)),
+            GameInput::Lane(lane) => self.move_to(lane),
+        }
     }
 
-    pub fn move_to(&mut self, location: i8) {
-        self.player_target_x = location;
+    fn move_to(&mut self, lane: usize) {
+        if lane != self.player_lane {
+            if let Some(dodge_animation) = &self.player_dodge_animation {
+                self.player.play_animation(dodge_animation);
+            }
+        }
+        self.player_lane = lane.min(self.lanes.count() - 1);
     }
 
-    pub fn check_collision(&self) -> bool {
-        for cube in &self.cubes {
-            if self.player.collides_with(cube) {
-                return true;
+    // The lane drawn closest to a horizontal position on the screen, 0 is the left edge and 1 the
+    // right one, lanes are projected at the player's depth so taps follow the camera
+    pub fn lane_at(&self, screen_x: f64) -> usize {
+        let view_projection = self.camera.view_projection();
+        let screen_distance = |lane: usize| {
+            let position =
+                view_projection * glm::vec4(self.lanes.x(lane), 0.0, -self.player.y(), 1.0);
+            let projected_x = (position.x / position.w + 1.0) as f64 / 2.0;
+            (projected_x - screen_x).abs()
+        };
+        (0..self.lanes.count())
+            .min_by(|&a, &b| screen_distance(a).total_cmp(&screen_distance(b)))
+            .unwrap()
+    }
+
+    fn respawn(&mut self, index: usize) {
+        self.entities[index] = Entity::spawn(
+            &self.manifest,
+            &self.assets,
+            &self.lanes,
+            &mut self.rng,
+            self.spawn_y,
+        );
+    }
+
+    fn update_score_indicator(&self) {
+        let text = if self.score_multiplier > 0.0 {
+            format!("{} x{}", self.score, SCORE_MULTIPLIER)
+        } else {
+            self.score.to_string()
+        };
+        if let Some(score_indicator) = &self.score_indicator {
+            score_indicator.set_inner_text(&text);
+        }
+    }
+
+    fn collect(&mut self, power_up: PowerUp) {
+        match power_up {
+            PowerUp::Shield => {
+                self.shield = true;
+                self.player.set_color(Color::GREEN);
+            }
+            PowerUp::SlowMotion => self.slow_motion = SLOW_MOTION_DURATION,
+            PowerUp::ScoreMultiplier => {
+                self.score_multiplier = SCORE_MULTIPLIER_DURATION;
+                self.update_score_indicator();
             }
         }
+    }
+
+    // Picks up touched power-ups, returns true when the player hit a hazard without a shield
+    fn check_collision(&mut self) -> bool {
+        for index in 0..self.entities.len() {
+            if !self.player.collides_with(&self.entities[index].cube) {
+                continue;
+            }
+            match self.entities[index].kind {
+                EntityKind::PowerUp(power_up) => self.collect(power_up),
+                _ if self.shield => {
+                    self.shield = false;
+                    self.player.reset_color();
+                    self.camera.shake(0.7);
+                }
+                _ => {
+                    self.camera.shake(1.0);
+                    return true;
+                }
+            }
+            self.respawn(index);
+        }
         return false;
     }
 
     pub fn score(&self) -> u32 {
         self.score
     }
+
+    pub fn stats(&self) -> RunStats {
+        RunStats {
+            duration: self.frame as f32 * STEP.as_secs_f32(),
+            obstacles_passed: self.obstacles_passed,
+            max_speed: self.max_speed,
+            rules: serde_json::to_string(&self.rules).unwrap(),
+            version: GAME_VERSION,
+        }
+    }
 }
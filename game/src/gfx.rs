@@ -1,16 +1,172 @@
 use std::mem;
 use std::ops::{Index, IndexMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use itertools::Itertools;
 use nalgebra_glm as glm;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 use crate::color::Color;
+use crate::texture::{Filter, Sampler, Texture};
+
+const TILE_HEIGHT: usize = 32;
+
+// Set once a thread pool is available, until then tiles are rasterized on the calling thread
+static PARALLEL_RASTERIZATION: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "parallel")]
+pub fn enable_parallel_rasterization() {
+    PARALLEL_RASTERIZATION.store(true, Ordering::Relaxed);
+}
+
+pub fn parallel_rasterization_enabled() -> bool {
+    cfg!(feature = "parallel") && PARALLEL_RASTERIZATION.load(Ordering::Relaxed)
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum LineMode {
+    Aliased,
+    AntiAliased,
+}
+
+enum Primitive {
+    Line {
+        from: (i32, i32, f32),
+        to: (i32, i32, f32),
+        colors: (Color, Color),
+        opacity: f32,
+    },
+    AntiAliasedLine {
+        from: glm::Vec3,
+        to: glm::Vec3,
+        colors: (Color, Color),
+        opacity: f32,
+    },
+    Triangle {
+        vertexes: [glm::Vec4; 3],
+        texcoords: [glm::Vec2; 3],
+        texture: usize,
+        sampler: Sampler,
+    },
+}
+
+impl Primitive {
+    fn y_range(&self) -> (f32, f32) {
+        match self {
+            Primitive::Line { from, to, .. } => {
+                let (from_y, to_y) = (from.1 as f32, to.1 as f32);
+                (from_y.min(to_y), from_y.max(to_y))
+            }
+            // Coverage spills into the row below the line
+            Primitive::AntiAliasedLine { from, to, .. } => {
+                (from[1].min(to[1]), from[1].max(to[1]) + 1.0)
+            }
+            Primitive::Triangle { vertexes, .. } => {
+                let [v0, v1, v2] = vertexes;
+                (v0[1].min(v1[1]).min(v2[1]), v0[1].max(v1[1]).max(v2[1]))
+            }
+        }
+    }
+
+    fn rasterize(&self, tile: &mut Tile, textures: &[Arc<Texture>]) {
+        match self {
+            Primitive::Line {
+                from,
+                to,
+                colors,
+                opacity,
+            } => draw_line(tile, *from, *to, *colors, *opacity),
+            Primitive::AntiAliasedLine {
+                from,
+                to,
+                colors,
+                opacity,
+            } => draw_line_anti_aliased(tile, *from, *to, *colors, *opacity),
+            Primitive::Triangle {
+                vertexes,
+                texcoords,
+                texture,
+                sampler,
+            } => draw_triangle(tile, vertexes, texcoords, &textures[*texture], *sampler),
+        }
+    }
+}
+
+// A horizontal band of the framebuffer, rows y_offset..(y_offset + height)
+struct Tile<'a> {
+    color: &'a mut [Color],
+    depth: &'a mut [f32],
+    width: usize,
+    y_offset: usize,
+    height: usize,
+}
+
+impl<'a> Tile<'a> {
+    #[inline]
+    fn put_pixel_if_possible(&mut self, x: i32, y: i32, z: f32, color: Color) {
+        let y = y - self.y_offset as i32;
+        if (0..self.width as i32).contains(&x)
+            && (0..self.height as i32).contains(&y)
+            && (0.0..=1.0).contains(&z)
+        {
+            let index = self.width * y as usize + x as usize;
+            let depth;
+            #[cfg(debug_assertions)]
+            {
+                depth = self.depth.get_mut(index).unwrap();
+            }
+            #[cfg(not(debug_assertions))]
+            {
+                depth = unsafe { self.depth.get_unchecked_mut(index) };
+            }
+            if z <= *depth {
+                *depth = z;
+                #[cfg(debug_assertions)]
+                {
+                    *self.color.get_mut(index).unwrap() = color;
+                }
+                #[cfg(not(debug_assertions))]
+                unsafe {
+                    *self.color.get_unchecked_mut(index) = color;
+                }
+            }
+        }
+    }
+
+    // Blends by coverage, only mostly covered pixels write depth so faint edges don't hide what's behind them
+    #[inline]
+    fn blend_pixel_if_possible(&mut self, x: i32, y: i32, z: f32, color: Color, coverage: f32) {
+        let y = y - self.y_offset as i32;
+        if coverage > 0.0
+            && (0..self.width as i32).contains(&x)
+            && (0..self.height as i32).contains(&y)
+            && (0.0..=1.0).contains(&z)
+        {
+            let index = self.width * y as usize + x as usize;
+            if z <= self.depth[index] {
+                if coverage >= 0.5 {
+                    self.depth[index] = z;
+                }
+                self.color[index] = self.color[index].lerp(color, coverage.min(1.0));
+            }
+        }
+    }
+}
 
 pub struct Framebuffer {
     color: Vec<Color>,
     depth: Vec<f32>,
     width: usize,
     height: usize,
+    primitives: Vec<Primitive>,
+    textures: Vec<Arc<Texture>>,
+    line_mode: LineMode,
+    // Replaces the filter models asked for when set
+    texture_filter: Option<Filter>,
+    // Applies to lines drawn from now on, translucent lines don't hide what's behind them
+    line_opacity: f32,
 }
 
 impl Index<[usize; 2]> for Framebuffer {
@@ -39,6 +195,11 @@ impl Framebuffer {
             depth: vec![f32::MAX; size],
             width,
             height,
+            primitives: Vec::new(),
+            textures: Vec::new(),
+            line_mode: LineMode::Aliased,
+            texture_filter: None,
+            line_opacity: 1.0,
         }
     }
 
@@ -51,11 +212,108 @@ impl Framebuffer {
         self.depth.resize(size, f32::MAX);
     }
 
+    pub fn line_mode(&self) -> LineMode {
+        self.line_mode
+    }
+
+    pub fn set_line_mode(&mut self, line_mode: LineMode) {
+        self.line_mode = line_mode;
+    }
+
+    pub fn texture_filter(&self) -> Option<Filter> {
+        self.texture_filter
+    }
+
+    pub fn set_texture_filter(&mut self, filter: Option<Filter>) {
+        self.texture_filter = filter;
+    }
+
+    pub fn set_line_opacity(&mut self, opacity: f32) {
+        self.line_opacity = opacity.clamp(0.0, 1.0);
+    }
+
     pub fn clear(&mut self, color: Color) {
         self.color.fill(color);
         self.depth.fill(f32::MAX);
     }
 
+    // Rasterizes everything drawn since the last call
+    pub fn rasterize(&mut self) {
+        let mut tile = Tile {
+            color: &mut self.color,
+            depth: &mut self.depth,
+            width: self.width,
+            y_offset: 0,
+            height: self.height,
+        };
+        for primitive in &self.primitives {
+            primitive.rasterize(&mut tile, &self.textures);
+        }
+        self.primitives.clear();
+        self.textures.clear();
+    }
+
+    // Waits for the thread pool when parallel rasterization is enabled, which the browser doesn't
+    // allow on the main thread, so only the benchmark worker uses it
+    pub fn rasterize_tiled(&mut self) {
+        if self.width == 0 || self.height == 0 {
+            self.primitives.clear();
+            self.textures.clear();
+            return;
+        }
+        let tile_count = self.height.div_ceil(TILE_HEIGHT);
+        let mut bins = vec![Vec::new(); tile_count];
+        for (index, primitive) in self.primitives.iter().enumerate() {
+            let (min_y, max_y) = primitive.y_range();
+            if max_y < 0.0 || min_y >= self.height as f32 {
+                continue;
+            }
+            let first = min_y.max(0.0) as usize / TILE_HEIGHT;
+            let last = (max_y as usize).min(self.height - 1) / TILE_HEIGHT;
+            for bin in &mut bins[first..=last] {
+                bin.push(index);
+            }
+        }
+
+        let width = self.width;
+        let primitives = &self.primitives;
+        let textures = &self.textures;
+        let tiles = self
+            .color
+            .chunks_mut(width * TILE_HEIGHT)
+            .zip(self.depth.chunks_mut(width * TILE_HEIGHT))
+            .zip(bins)
+            .enumerate()
+            .map(|(index, ((color, depth), bin))| {
+                let height = color.len() / width;
+                let tile = Tile {
+                    color,
+                    depth,
+                    width,
+                    y_offset: index * TILE_HEIGHT,
+                    height,
+                };
+                (tile, bin)
+            })
+            .collect_vec();
+        let rasterize_tile = |(mut tile, bin): (Tile, Vec<usize>)| {
+            for index in bin {
+                primitives[index].rasterize(&mut tile, textures);
+            }
+        };
+        #[cfg(feature = "parallel")]
+        if parallel_rasterization_enabled() {
+            tiles.into_par_iter().for_each(rasterize_tile);
+        } else {
+            tiles.into_iter().for_each(rasterize_tile);
+        }
+        #[cfg(not(feature = "parallel"))]
+        tiles.into_iter().for_each(rasterize_tile);
+
+        self.primitives.clear();
+        self.textures.clear();
+    }
+
     #[inline]
     pub fn as_slice(&self) -> &[u8] {
         bytemuck::cast_slice(self.color.as_slice())
@@ -75,6 +333,26 @@ impl Framebuffer {
     fn calculate_index(&self, x: usize, y: usize) -> usize {
         self.width * y + x
     }
+
+    fn texture_index(&mut self, texture: &Arc<Texture>) -> usize {
+        match self
+            .textures
+            .iter()
+            .position(|bound| Arc::ptr_eq(bound, texture))
+        {
+            Some(index) => index,
+            None => {
+                self.textures.push(texture.clone());
+                self.textures.len() - 1
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum LineColor<'a> {
+    Uniform(Color),
+    PerVertex(&'a [Color]),
 }
 
 pub fn draw_line_list_indexed(
@@ -82,7 +360,7 @@ pub fn draw_line_list_indexed(
     vertexes: &[glm::Vec3],
     indexes: &[u32],
     transform: &glm::Mat4x4,
-    color: Color,
+    color: LineColor,
 ) {
     let line_indexes = indexes.iter().tuples();
     let vertexes = transform_data(vertexes, transform).collect_vec();
@@ -99,9 +377,79 @@ pub fn draw_line_list_indexed(
         let from = vertexes[from_i as usize];
         let to = vertexes[to_i as usize];
 
-        let from = (from[0] as i32, from[1] as i32, from[2]);
-        let to = (to[0] as i32, to[1] as i32, to[2]);
-        draw_line(framebuffer, from, to, color);
+        let colors = match color {
+            LineColor::Uniform(color) => (color, color),
+            LineColor::PerVertex(colors) => (colors[from_i as usize], colors[to_i as usize]),
+        };
+        let opacity = framebuffer.line_opacity;
+        let line = match framebuffer.line_mode {
+            LineMode::Aliased => Primitive::Line {
+                from: (from[0] as i32, from[1] as i32, from[2]),
+                to: (to[0] as i32, to[1] as i32, to[2]),
+                colors,
+                opacity,
+            },
+            LineMode::AntiAliased => Primitive::AntiAliasedLine {
+                from,
+                to,
+                colors,
+                opacity,
+            },
+        };
+        framebuffer.primitives.push(line);
+    }
+}
+
+pub fn draw_triangle_list_indexed(
+    framebuffer: &mut Framebuffer,
+    vertexes: &[glm::Vec3],
+    texcoords: &[glm::Vec2],
+    indexes: &[u32],
+    transform: &glm::Mat4x4,
+    texture: &Arc<Texture>,
+    mut sampler: Sampler,
+) {
+    let texture = framebuffer.texture_index(texture);
+    if let Some(filter) = framebuffer.texture_filter {
+        sampler.filter = filter;
+    }
+    let vertexes = transform_data(vertexes, transform).collect_vec();
+    let x_screen_transform = framebuffer.width() as f32 / 2.0;
+    let y_screen_transform = framebuffer.height() as f32 / 2.0;
+
+    for (&a, &b, &c) in indexes.iter().tuples() {
+        let (a, b, c) = (a as usize, b as usize, c as usize);
+        let triangle = [vertexes[a], vertexes[b], vertexes[c]];
+        if (0..3).any(|i| {
+            triangle.iter().all(|vertex| vertex[i] > vertex[3])
+                || triangle.iter().all(|vertex| -vertex[i] > vertex[3])
+        }) {
+            continue;
+        }
+        // Triangles crossing the camera plane are dropped instead of being clipped
+        if triangle.iter().any(|vertex| vertex[3] <= 0.0) {
+            continue;
+        }
+        let screen = triangle.map(|vertex| {
+            let inverse_w = 1.0 / vertex[3];
+            glm::vec4(
+                (vertex[0] * inverse_w + 1.0) * x_screen_transform,
+                (vertex[1] * inverse_w + 1.0) * y_screen_transform,
+                vertex[2] * inverse_w,
+                inverse_w,
+            )
+        });
+        let texcoords = [
+            texcoords[a] * screen[0][3],
+            texcoords[b] * screen[1][3],
+            texcoords[c] * screen[2][3],
+        ];
+        framebuffer.primitives.push(Primitive::Triangle {
+            vertexes: screen,
+            texcoords,
+            texture,
+            sampler,
+        });
     }
 }
 
@@ -154,56 +502,39 @@ fn viewport_transform(
 }
 
 fn draw_line(
-    framebuffer: &mut Framebuffer,
+    tile: &mut Tile,
     from: (i32, i32, f32),
     to: (i32, i32, f32),
-    color: Color,
+    colors: (Color, Color),
+    opacity: f32,
 ) {
+    let mut plot = |x: i32, y: i32, z: f32, color: Color| {
+        if opacity < 1.0 {
+            tile.blend_pixel_if_possible(x, y, z, color, opacity);
+        } else {
+            tile.put_pixel_if_possible(x, y, z, color);
+        }
+    };
     let (mut x0, mut y0, mut z0) = from;
     let (mut x1, mut y1, mut z1) = to;
+    let (mut c0, mut c1) = colors;
     let run = x1 - x0;
     let rise = y1 - y0;
     let dive = z1 - z0;
-    let width = framebuffer.width();
-    let height = framebuffer.height();
-    let width_range = 0..width as i32;
-    let height_range = 0..height as i32;
-    let depth_range = 0.0..=1.0;
-    let mut put_pixel_if_possible = |x: i32, y: i32, z: f32| {
-        if width_range.contains(&x) && height_range.contains(&y) && depth_range.contains(&z) {
-            let index = framebuffer.calculate_index(x as usize, y as usize);
-            let depth;
-            #[cfg(debug_assertions)]
-            {
-                depth = framebuffer.depth.get_mut(index).unwrap();
-            }
-            #[cfg(not(debug_assertions))]
-            {
-                depth = unsafe { framebuffer.depth.get_unchecked_mut(index) };
-            }
-            if z <= *depth {
-                *depth = z;
-                #[cfg(debug_assertions)]
-                {
-                    *framebuffer.color.get_mut(index).unwrap() = color;
-                }
-                #[cfg(not(debug_assertions))]
-                unsafe {
-                    *framebuffer.color.get_unchecked_mut(index) = color;
-                }
-            }
-        }
-    };
     if run == 0 {
         let z_delta = dive / rise as f32;
+        let t_delta = 1.0 / rise.abs() as f32;
         let mut z = z0;
+        let mut t = 0.0;
         if y0 > y1 {
             mem::swap(&mut y0, &mut y1);
             mem::swap(&mut z0, &mut z1);
+            mem::swap(&mut c0, &mut c1);
         }
         for y in y0..=y1 {
-            put_pixel_if_possible(x0, y, z);
+            plot(x0, y, z, c0.lerp(c1, t));
             z += z_delta;
+            t += t_delta;
         }
     } else {
         let m = rise as f32 / run as f32;
@@ -214,18 +545,22 @@ fn draw_line(
             let mut threshold = run.abs();
             let threshold_inc = threshold * 2;
             let z_delta = dive / run as f32;
+            let t_delta = 1.0 / run.abs() as f32;
             let mut z = z0;
+            let mut t = 0.0;
             let mut y;
             if x0 > x1 {
                 mem::swap(&mut x0, &mut x1);
                 mem::swap(&mut z0, &mut z1);
+                mem::swap(&mut c0, &mut c1);
                 y = y1;
             } else {
                 y = y0;
             }
             for x in x0..=x1 {
-                put_pixel_if_possible(x, y, z);
+                plot(x, y, z, c0.lerp(c1, t));
                 z += z_delta;
+                t += t_delta;
                 offset += delta;
                 if offset >= threshold {
                     y = y + adjust;
@@ -237,18 +572,22 @@ fn draw_line(
             let mut threshold = rise.abs();
             let threshold_inc = threshold * 2;
             let z_delta = dive / rise as f32;
+            let t_delta = 1.0 / rise.abs() as f32;
             let mut z = z0;
+            let mut t = 0.0;
             let mut x;
             if y0 > y1 {
                 mem::swap(&mut y0, &mut y1);
                 mem::swap(&mut z0, &mut z1);
+                mem::swap(&mut c0, &mut c1);
                 x = x1;
             } else {
                 x = x0;
             }
             for y in y0..=y1 {
-                put_pixel_if_possible(x, y, z);
+                plot(x, y, z, c0.lerp(c1, t));
                 z += z_delta;
+                t += t_delta;
                 offset += delta;
                 if offset >= threshold {
                     x = x.wrapping_add(adjust);
@@ -258,3 +597,143 @@ fn draw_line(
         }
     }
 }
+
+fn fractional(value: f32) -> f32 {
+    value - value.floor()
+}
+
+// Xiaolin Wu's algorithm, depth and colour are interpolated along the major axis like in draw_line
+fn draw_line_anti_aliased(
+    tile: &mut Tile,
+    from: glm::Vec3,
+    to: glm::Vec3,
+    colors: (Color, Color),
+    opacity: f32,
+) {
+    let (mut from, mut to) = (from, to);
+    let (mut c0, mut c1) = colors;
+    let steep = (to[1] - from[1]).abs() > (to[0] - from[0]).abs();
+    if steep {
+        from = glm::vec3(from[1], from[0], from[2]);
+        to = glm::vec3(to[1], to[0], to[2]);
+    }
+    if from[0] > to[0] {
+        mem::swap(&mut from, &mut to);
+        mem::swap(&mut c0, &mut c1);
+    }
+    let mut plot = |x: i32, y: i32, z: f32, color: Color, coverage: f32| {
+        let coverage = coverage * opacity;
+        if steep {
+            tile.blend_pixel_if_possible(y, x, z, color, coverage);
+        } else {
+            tile.blend_pixel_if_possible(x, y, z, color, coverage);
+        }
+    };
+
+    let run = to[0] - from[0];
+    let gradient = if run == 0.0 {
+        1.0
+    } else {
+        (to[1] - from[1]) / run
+    };
+
+    let x_start = from[0].round();
+    let y_start = from[1] + gradient * (x_start - from[0]);
+    let x_gap = 1.0 - fractional(from[0] + 0.5);
+    let x0 = x_start as i32;
+    plot(
+        x0,
+        y_start.floor() as i32,
+        from[2],
+        c0,
+        (1.0 - fractional(y_start)) * x_gap,
+    );
+    plot(
+        x0,
+        y_start.floor() as i32 + 1,
+        from[2],
+        c0,
+        fractional(y_start) * x_gap,
+    );
+
+    let x_end = to[0].round();
+    let y_end = to[1] + gradient * (x_end - to[0]);
+    let x_gap = fractional(to[0] + 0.5);
+    let x1 = x_end as i32;
+    plot(
+        x1,
+        y_end.floor() as i32,
+        to[2],
+        c1,
+        (1.0 - fractional(y_end)) * x_gap,
+    );
+    plot(
+        x1,
+        y_end.floor() as i32 + 1,
+        to[2],
+        c1,
+        fractional(y_end) * x_gap,
+    );
+
+    let steps = (x1 - x0).max(1) as f32;
+    let mut y = y_start + gradient;
+    for x in (x0 + 1)..x1 {
+        let t = (x - x0) as f32 / steps;
+        let z = from[2] + (to[2] - from[2]) * t;
+        let color = c0.lerp(c1, t);
+        plot(x, y.floor() as i32, z, color, 1.0 - fractional(y));
+        plot(x, y.floor() as i32 + 1, z, color, fractional(y));
+        y += gradient;
+    }
+}
+
+fn edge(a: &glm::Vec4, b: &glm::Vec4, x: f32, y: f32) -> f32 {
+    (b[0] - a[0]) * (y - a[1]) - (b[1] - a[1]) * (x - a[0])
+}
+
+// Expects screen space vertexes with 1/w in the last component and texture coordinates divided by w
+fn draw_triangle(
+    tile: &mut Tile,
+    vertexes: &[glm::Vec4; 3],
+    texcoords: &[glm::Vec2; 3],
+    texture: &Texture,
+    sampler: Sampler,
+) {
+    let [v0, v1, v2] = vertexes;
+    let area = edge(v0, v1, v2[0], v2[1]);
+    if area == 0.0 {
+        return;
+    }
+
+    let max_x = tile.width as f32 - 1.0;
+    let first_y = tile.y_offset as f32;
+    let last_y = (tile.y_offset + tile.height) as f32 - 1.0;
+    let min_x = v0[0].min(v1[0]).min(v2[0]).floor().clamp(0.0, max_x) as usize;
+    let max_x = v0[0].max(v1[0]).max(v2[0]).ceil().clamp(0.0, max_x) as usize;
+    let min_y = v0[1].min(v1[1]).min(v2[1]).floor().clamp(first_y, last_y) as usize;
+    let max_y = v0[1].max(v1[1]).max(v2[1]).ceil().clamp(first_y, last_y) as usize;
+
+    for y in min_y..=max_y {
+        let sample_y = y as f32 + 0.5;
+        for x in min_x..=max_x {
+            let sample_x = x as f32 + 0.5;
+            let w0 = edge(v1, v2, sample_x, sample_y) / area;
+            let w1 = edge(v2, v0, sample_x, sample_y) / area;
+            let w2 = 1.0 - w0 - w1;
+            if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                continue;
+            }
+            let z = w0 * v0[2] + w1 * v1[2] + w2 * v2[2];
+            if !(0.0..=1.0).contains(&z) {
+                continue;
+            }
+            let index = tile.width * (y - tile.y_offset) + x;
+            if z <= tile.depth[index] {
+                let inverse_w = w0 * v0[3] + w1 * v1[3] + w2 * v2[3];
+                let uv = (texcoords[0] * w0 + texcoords[1] * w1 + texcoords[2] * w2) / inverse_w;
+                tile.depth[index] = z;
+                tile.color[index] = texture.sample(uv, sampler);
+            }
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+use wasm_bindgen::JsCast;
+use winit::event::{ElementState, MouseButton, VirtualKeyCode};
+
+const KEY_BINDINGS_KEY: &str = "key bindings";
+// Fraction of the canvas width a pointer has to travel to count as a swipe instead of a tap
+const SWIPE_DISTANCE: f64 = 0.08;
+const STICK_THRESHOLD: f64 = 0.5;
+// Standard gamepad mapping, https://w3c.github.io/gamepad/#remapping
+const DPAD_LEFT: u32 = 14;
+const DPAD_RIGHT: u32 = 15;
+const START: u32 = 9;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Action {
+    Left,
+    Right,
+    Pause,
+    // Horizontal position on the canvas, 0 is the left edge and 1 the right one
+    Tap(f64),
+}
+
+// Stored by the controls page, keys are KeyboardEvent.code values
+#[derive(Deserialize)]
+struct StoredKeyBindings {
+    move_left: Vec<String>,
+    move_right: Vec<String>,
+    #[serde(default)]
+    pause: Vec<String>,
+}
+
+pub struct KeyBindings {
+    move_left: Vec<VirtualKeyCode>,
+    move_right: Vec<VirtualKeyCode>,
+    pause: Vec<VirtualKeyCode>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            move_left: vec![VirtualKeyCode::A, VirtualKeyCode::Left],
+            move_right: vec![VirtualKeyCode::D, VirtualKeyCode::Right],
+            pause: vec![VirtualKeyCode::Escape, VirtualKeyCode::P],
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn load(storage: &web_sys::Storage) -> Self {
+        let stored = storage
+            .get_item(KEY_BINDINGS_KEY)
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str::<StoredKeyBindings>(&json).ok());
+        match stored {
+            Some(stored) => {
+                let keys = |codes: &[String]| {
+                    codes
+                        .iter()
+                        .filter_map(|code| key_from_code(code))
+                        .collect::<Vec<_>>()
+                };
+                let defaults = Self::default();
+                let or_default = |keys: Vec<VirtualKeyCode>, default| {
+                    if keys.is_empty() {
+                        default
+                    } else {
+                        keys
+                    }
+                };
+                Self {
+                    move_left: or_default(keys(&stored.move_left), defaults.move_left),
+                    move_right: or_default(keys(&stored.move_right), defaults.move_right),
+                    pause: or_default(keys(&stored.pause), defaults.pause),
+                }
+            }
+            None => Self::default(),
+        }
+    }
+
+    fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        if self.move_left.contains(&key) {
+            Some(Action::Left)
+        } else if self.move_right.contains(&key) {
+            Some(Action::Right)
+        } else if self.pause.contains(&key) {
+            Some(Action::Pause)
+        } else {
+            None
+        }
+    }
+}
+
+// Turns keyboard, pointer (mouse and touch) and gamepad input into game actions
+pub struct Input {
+    bindings: KeyBindings,
+    pressed_keys: HashSet<VirtualKeyCode>,
+    just_pressed_keys: HashSet<VirtualKeyCode>,
+    pointer_x: f64,
+    pointer_down_x: Option<f64>,
+    gamepad_direction: i8,
+    gamepad_start: bool,
+    actions: Vec<Action>,
+}
+
+impl Input {
+    pub fn new(bindings: KeyBindings) -> Self {
+        Self {
+            bindings,
+            pressed_keys: HashSet::new(),
+            just_pressed_keys: HashSet::new(),
+            pointer_x: 0.0,
+            pointer_down_x: None,
+            gamepad_direction: 0,
+            gamepad_start: false,
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn key_input(&mut self, key: VirtualKeyCode, state: ElementState) {
+        if state == ElementState::Pressed {
+            if self.pressed_keys.insert(key) {
+                self.just_pressed_keys.insert(key);
+                if let Some(action) = self.bindings.action(key) {
+                    self.actions.push(action);
+                }
+            }
+        } else {
+            self.pressed_keys.remove(&key);
+        }
+    }
+
+    pub fn just_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.just_pressed_keys.contains(&key)
+    }
+
+    pub fn pointer_moved(&mut self, x: f64) {
+        self.pointer_x = x;
+    }
+
+    // Touches arrive as pointer events too, a short press is a tap and a long horizontal drag is a swipe
+    pub fn pointer_input(&mut self, state: ElementState, button: MouseButton, width: f64) {
+        if button != MouseButton::Left || width <= 0.0 {
+            return;
+        }
+        match state {
+            ElementState::Pressed => self.pointer_down_x = Some(self.pointer_x),
+            ElementState::Released => {
+                if let Some(down_x) = self.pointer_down_x.take() {
+                    let distance = (self.pointer_x - down_x) / width;
+                    if distance <= -SWIPE_DISTANCE {
+                        self.actions.push(Action::Left);
+                    } else if distance >= SWIPE_DISTANCE {
+                        self.actions.push(Action::Right);
+                    } else {
+                        self.actions.push(Action::Tap(down_x / width));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn poll_gamepads(&mut self) {
+        let gamepads =
+            match web_sys::window().and_then(|window| window.navigator().get_gamepads().ok()) {
+                Some(gamepads) => gamepads,
+                None => return,
+            };
+        let mut direction = 0;
+        let mut start = false;
+        for gamepad in gamepads.iter() {
+            let gamepad = match gamepad.dyn_into::<web_sys::Gamepad>() {
+                Ok(gamepad) => gamepad,
+                Err(_) => continue,
+            };
+            let buttons = gamepad.buttons();
+            let pressed = |index: u32| {
+                buttons
+                    .get(index)
+                    .dyn_into::<web_sys::GamepadButton>()
+                    .map(|button| button.pressed())
+                    .unwrap_or(false)
+            };
+            start |= pressed(START);
+            let stick = gamepad.axes().get(0).as_f64().unwrap_or(0.0);
+            if pressed(DPAD_LEFT) || stick <= -STICK_THRESHOLD {
+                direction = -1;
+            } else if pressed(DPAD_RIGHT) || stick >= STICK_THRESHOLD {
+                direction = 1;
+            }
+        }
+        // Holding a direction moves only once, like a key press
+        if direction != self.gamepad_direction {
+            match direction {
+                -1 => self.actions.push(Action::Left),
+                1 => self.actions.push(Action::Right),
+                _ => (),
+            }
+            self.gamepad_direction = direction;
+        }
+        if start && !self.gamepad_start {
+            self.actions.push(Action::Pause);
+        }
+        self.gamepad_start = start;
+    }
+
+    pub fn take_actions(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.actions)
+    }
+
+    pub fn end_frame(&mut self) {
+        self.just_pressed_keys.clear();
+    }
+}
+
+fn key_from_code(code: &str) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+    const LETTERS: [VirtualKeyCode; 26] = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    const DIGITS: [VirtualKeyCode; 10] =
+        [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    if let Some(letter) = code.strip_prefix("Key") {
+        let letter = letter.chars().next().filter(|_| letter.len() == 1)?;
+        return LETTERS
+            .get((letter as u8).checked_sub(b'A')? as usize)
+            .copied();
+    }
+    if let Some(digit) = code.strip_prefix("Digit") {
+        return DIGITS.get(digit.parse::<usize>().ok()?).copied();
+    }
+    Some(match code {
+        "ArrowLeft" => Left,
+        "ArrowRight" => Right,
+        "ArrowUp" => Up,
+        "ArrowDown" => Down,
+        "Space" => Space,
+        "Enter" => Return,
+        "Escape" => Escape,
+        "Tab" => Tab,
+        "Comma" => Comma,
+        "Period" => Period,
+        "Semicolon" => Semicolon,
+        "Quote" => Apostrophe,
+        "Slash" => Slash,
+        "Backslash" => Backslash,
+        "BracketLeft" => LBracket,
+        "BracketRight" => RBracket,
+        "Minus" => Minus,
+        "Equal" => Equals,
+        "ShiftLeft" => LShift,
+        "ShiftRight" => RShift,
+        "ControlLeft" => LControl,
+        "ControlRight" => RControl,
+        "AltLeft" => LAlt,
+        "AltRight" => RAlt,
+        _ => return None,
+    })
+}
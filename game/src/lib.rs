@@ -1,9 +1,12 @@
 use instant::Instant;
 use nalgebra_glm as glm;
-use std::collections::HashSet;
+use std::cell::Cell;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use winit::event::{ElementState, MouseButton, VirtualKeyCode};
+#[cfg(feature = "parallel")]
+use wasm_bindgen_futures::JsFuture;
+use winit::event::VirtualKeyCode;
 use winit::{
     event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
@@ -11,25 +14,55 @@ use winit::{
     window::WindowBuilder,
 };
 
+use crate::assets::{Asset, AssetLibrary, Manifest};
+use crate::camera::{Camera, CameraMode};
 use crate::cube::Cube;
 use crate::game::Game;
+use crate::input::{Action, Input, KeyBindings};
 use color::Color;
 use web_gl::WebGLContext;
 
 use crate::model::Model;
+use crate::replay::{GameInput, Replay};
+use crate::resolution::ResolutionScaler;
+use crate::rules::{Difficulty, GameRules};
+use crate::texture::Filter;
 
+mod animation;
+mod assets;
+mod bench;
+mod camera;
+mod collision;
 mod color;
 mod cube;
 mod game;
 mod gfx;
+mod input;
 mod logging;
 pub mod model;
+mod replay;
+mod resolution;
+mod rules;
+mod texture;
 mod web_gl;
 
-const RESOLUTION_SCALE: usize = 1;
+const MANIFEST_URL: &str = "assets/manifest.json";
 
-#[wasm_bindgen(start)]
-pub fn main() -> Result<(), JsValue> {
+// Only for workers, rayon blocks the calling thread while the pool works and the main thread can't
+#[cfg(feature = "parallel")]
+#[wasm_bindgen(js_name = initThreadPool)]
+pub async fn init_thread_pool(threads: usize) -> Result<(), JsValue> {
+    if web_sys::window().is_some() {
+        return Err("The thread pool can only be used from a worker".into());
+    }
+    JsFuture::from(wasm_bindgen_rayon::init_thread_pool(threads)).await?;
+    gfx::enable_parallel_rasterization();
+    Ok(())
+}
+
+// Not a start function, it would also run on every worker of the thread pool
+#[wasm_bindgen]
+pub async fn run() -> Result<(), JsValue> {
     let html_window = web_sys::window().ok_or("Couldn't get window")?;
     let document = html_window.document().ok_or("Couldn't get document")?;
     let canvas = document
@@ -44,12 +77,19 @@ pub fn main() -> Result<(), JsValue> {
         .get_element_by_id("score")
         .ok_or("Score indicator not found")?
         .dyn_into::<web_sys::HtmlSpanElement>()?;
+    let pause_overlay = document
+        .get_element_by_id("pause_overlay")
+        .ok_or("Pause overlay not found")?
+        .dyn_into::<web_sys::HtmlElement>()?;
     let session_storage = html_window.session_storage()?.unwrap();
+    let local_storage = html_window.local_storage()?.unwrap();
+
+    let manifest = Manifest::load(MANIFEST_URL).await?;
+    let assets = AssetLibrary::load(&manifest).await?;
 
     let pixel_ratio = html_window.device_pixel_ratio();
     let mut width = canvas.client_width() as f64 * pixel_ratio;
     let mut height = canvas.client_height() as f64 * pixel_ratio;
-    let mut mouse_x = 0.0;
 
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
@@ -62,30 +102,70 @@ pub fn main() -> Result<(), JsValue> {
     window.canvas().set_width(width as u32);
     window.canvas().set_height(height as u32);
 
-    let context = WebGLContext::new(&window.canvas())?;
+    let mut context = WebGLContext::new(&window.canvas())?;
     context.bind_all_objects();
 
     window.canvas().focus().unwrap();
 
-    let mut framebuffer = gfx::Framebuffer::new(
-        width as usize / RESOLUTION_SCALE,
-        height as usize / RESOLUTION_SCALE,
-    );
+    let mut resolution_scaler = ResolutionScaler::new();
+    let (framebuffer_width, framebuffer_height) = resolution_scaler.scaled_size(width, height);
+    let mut framebuffer = gfx::Framebuffer::new(framebuffer_width, framebuffer_height);
 
-    let mut game = Game::start(15, score_indicator);
+    // Set by the pages that let you watch a replay instead of playing
+    let watched_replay = session_storage.get_item("watch replay")?;
+    session_storage.remove_item("watch replay")?;
+    let mut game = match watched_replay {
+        Some(json) => Game::watch(Replay::from_json(&json)?, manifest, assets, score_indicator),
+        None => {
+            // Set when racing a run from the Hall of Fame, the player gets the same world as the ghost
+            let ghost = match session_storage.get_item("ghost replay")? {
+                Some(json) => Some(Replay::from_json(&json)?),
+                None => None,
+            };
+            // Picked on the main menu, runs are submitted under the same difficulty
+            let difficulty = session_storage
+                .get_item("difficulty")?
+                .and_then(|name| Difficulty::from_name(&name))
+                .unwrap_or(Difficulty::Normal);
+            let rules = match &ghost {
+                Some(ghost) => ghost.rules.clone(),
+                None => GameRules::preset(difficulty),
+            };
+            // Issued along with the run ticket, guests play with the ghost's or a random one
+            let seed = session_storage
+                .get_item("run seed")?
+                .and_then(|seed| seed.parse().ok())
+                .or(ghost.as_ref().map(|ghost| ghost.seed))
+                .unwrap_or_else(rand::random);
+            let mut game = Game::start(rules, seed, manifest, assets, score_indicator);
+            if let Some(ghost) = ghost {
+                game.race(ghost)?;
+            }
+            game
+        }
+    };
+    session_storage.remove_item("ghost replay")?;
+
+    let mut input = Input::new(KeyBindings::load(&local_storage));
 
-    let mut pressed_keys = HashSet::new();
-    let mut just_pressed_keys = HashSet::new();
+    // Hidden tabs stop getting frames, so the game is paused as soon as the page is hidden
+    let page_hidden = Rc::new(Cell::new(false));
+    {
+        let page_hidden = page_hidden.clone();
+        let doc = document.clone();
+        let on_visibility_change = Closure::wrap(Box::new(move || {
+            if doc.hidden() {
+                page_hidden.set(true);
+            }
+        }) as Box<dyn FnMut()>);
+        document.add_event_listener_with_callback(
+            "visibilitychange",
+            on_visibility_change.as_ref().unchecked_ref(),
+        )?;
+        on_visibility_change.forget();
+    }
 
-    let zoom = glm::scaling(&glm::vec3(1.0, 1.0, 1.0));
-    let view = glm::look_at(
-        &glm::vec3(0.0, 2.0, 4.0),
-        &glm::vec3(0.0, 0.5, 0.0),
-        &glm::vec3(0.0, 1.0, 0f32),
-    );
-    let projection =
-        glm::perspective_fov_zo(45_f32.to_radians(), width as f32, height as f32, 0.1, 100.0);
-    let mut camera = projection * view * zoom;
+    game.camera_mut().set_aspect(width as f32, height as f32);
 
     let program_start = Instant::now();
     let mut last_frame_time = program_start;
@@ -100,33 +180,30 @@ pub fn main() -> Result<(), JsValue> {
                 ..
             } => *control_flow = ControlFlow::Exit,
             Event::WindowEvent {
-                event: WindowEvent::KeyboardInput { input, .. },
+                event: WindowEvent::KeyboardInput { input: event, .. },
                 ..
             } => {
-                if let Some(key) = input.virtual_keycode {
-                    if input.state == ElementState::Pressed {
-                        if pressed_keys.insert(key) {
-                            just_pressed_keys.insert(key);
-                        }
-                    } else {
-                        pressed_keys.remove(&key);
-                    }
+                if let Some(key) = event.virtual_keycode {
+                    input.key_input(key, event.state);
                 }
             }
             Event::WindowEvent {
                 event: WindowEvent::MouseInput { state, button, .. },
                 ..
             } => {
-                if state == ElementState::Pressed && button == MouseButton::Left {
-                    let location = (mouse_x / (width / 3.0)) as i8;
-                    game.move_to(location);
-                }
+                input.pointer_input(state, button, width);
             }
             Event::WindowEvent {
                 event: WindowEvent::CursorMoved { position, .. },
                 ..
             } => {
-                mouse_x = position.x;
+                input.pointer_moved(position.x);
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Focused(false),
+                ..
+            } => {
+                game.pause();
             }
             Event::WindowEvent {
                 event: WindowEvent::Resized(_),
@@ -156,41 +233,90 @@ pub fn main() -> Result<(), JsValue> {
 
                     window.canvas().set_width(width as u32);
                     window.canvas().set_height(height as u32);
-                    context.resize(width as i32, height as i32);
-                    framebuffer.resize(
-                        width as usize / RESOLUTION_SCALE,
-                        height as usize / RESOLUTION_SCALE,
-                    );
-                    let projection = glm::perspective_fov_zo(
-                        45_f32.to_radians(),
-                        width as f32,
-                        height as f32,
-                        0.1,
-                        100.0,
-                    );
-                    camera = projection * view * zoom;
+                    context.resize(width as i32, height as i32).unwrap();
+                    let (framebuffer_width, framebuffer_height) =
+                        resolution_scaler.scaled_size(width, height);
+                    framebuffer.resize(framebuffer_width, framebuffer_height);
+                    game.camera_mut().set_aspect(width as f32, height as f32);
                 }
 
                 framebuffer.clear(Color::BLACK);
 
-                if just_pressed_keys.contains(&VirtualKeyCode::A)
-                    || just_pressed_keys.contains(&VirtualKeyCode::Left)
-                {
-                    game.move_left();
+                input.poll_gamepads();
+                if page_hidden.replace(false) {
+                    game.pause();
+                }
+                for action in input.take_actions() {
+                    match action {
+                        Action::Pause => game.toggle_pause(),
+                        // Touch screens have no pause key, tapping anywhere resumes
+                        Action::Tap(_) if game.is_paused() => game.resume(),
+                        _ if game.is_paused() => (),
+                        Action::Left => game.input(GameInput::Left),
+                        Action::Right => game.input(GameInput::Right),
+                        Action::Tap(x) => game.input(GameInput::Lane(game.lane_at(x))),
+                    }
+                }
+                if input.just_pressed(VirtualKeyCode::L) {
+                    framebuffer.set_line_mode(match framebuffer.line_mode() {
+                        gfx::LineMode::AntiAliased => gfx::LineMode::Aliased,
+                        gfx::LineMode::Aliased => gfx::LineMode::AntiAliased,
+                    });
+                }
+                // Cycles from the filters the models came with to forced nearest and bilinear
+                if input.just_pressed(VirtualKeyCode::T) {
+                    framebuffer.set_texture_filter(match framebuffer.texture_filter() {
+                        None => Some(Filter::Nearest),
+                        Some(Filter::Nearest) => Some(Filter::Bilinear),
+                        Some(Filter::Bilinear) => None,
+                    });
+                }
+                if input.just_pressed(VirtualKeyCode::C) {
+                    let mode = match game.camera().mode() {
+                        CameraMode::Fixed => CameraMode::Follow,
+                        CameraMode::Follow => CameraMode::Fixed,
+                    };
+                    game.camera_mut().set_mode(mode);
                 }
-                if just_pressed_keys.contains(&VirtualKeyCode::D)
-                    || just_pressed_keys.contains(&VirtualKeyCode::Right)
-                {
-                    game.move_right();
+                if game.advance(&delta_time) {
+                    if game.is_replay() {
+                        html_window.location().replace("/replays.html").unwrap();
+                    } else {
+                        session_storage
+                            .set_item("score", &game.score().to_string())
+                            .unwrap();
+                        session_storage
+                            .set_item("difficulty", game.rules().difficulty.name())
+                            .unwrap();
+                        session_storage
+                            .set_item("last replay", &game.replay().to_json())
+                            .unwrap();
+                        session_storage
+                            .set_item(
+                                "run stats",
+                                &serde_json::to_string(&game.stats()).unwrap(),
+                            )
+                            .unwrap();
+                        html_window.location().replace("/game-over.html").unwrap();
+                    }
                 }
-                game.advance(&delta_time);
-                game.draw(&mut framebuffer, &camera);
-                if game.check_collision() {
-                    session_storage
-                        .set_item("score", &game.score().to_string())
-                        .unwrap();
-                    html_window.location().replace("/game-over.html").unwrap();
+                match game.pause_text() {
+                    Some(text) => {
+                        pause_overlay.set_inner_text(&text);
+                        pause_overlay
+                            .style()
+                            .set_property("display", "flex")
+                            .unwrap();
+                    }
+                    None => {
+                        pause_overlay
+                            .style()
+                            .set_property("display", "none")
+                            .unwrap();
+                    }
                 }
+                game.draw(&mut framebuffer);
+                framebuffer.rasterize();
 
                 context
                     .update_texture(
@@ -202,8 +328,14 @@ pub fn main() -> Result<(), JsValue> {
 
                 context.draw();
 
+                if resolution_scaler.update(current_frame_time.elapsed(), delta_time) {
+                    let (framebuffer_width, framebuffer_height) =
+                        resolution_scaler.scaled_size(width, height);
+                    framebuffer.resize(framebuffer_width, framebuffer_height);
+                }
+
                 last_frame_time = current_frame_time;
-                just_pressed_keys.clear();
+                input.end_frame();
             }
             _ => (),
         }
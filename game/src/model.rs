@@ -1,19 +1,25 @@
 use std::collections::HashSet;
 use std::mem::size_of;
+use std::sync::Arc;
 
 use gltf::json::accessor::ComponentType;
 use itertools::Itertools;
 use nalgebra_glm as glm;
+use wasm_bindgen::JsValue;
 
+use crate::collision::Aabb;
+use crate::texture::{Filter, Sampler, Texture, Wrap};
 use crate::{gfx, Color};
 
 pub struct BufferView {
+    buffer: usize,
     offset: usize,
     length: usize,
 }
 
 impl BufferView {
-    fn look(&self, buffer: &[u8]) -> &[glm::Vec3] {
+    fn look<'a>(&self, buffers: &'a [Vec<u8>]) -> &'a [glm::Vec3] {
+        let buffer = &buffers[self.buffer];
         unsafe {
             std::slice::from_raw_parts(
                 buffer[self.offset..(self.offset + self.length)].as_ptr() as *const glm::Vec3,
@@ -48,11 +54,24 @@ fn triangles_to_lines_index(triangles: &[u32]) -> Vec<u32> {
         .collect()
 }
 
+struct Surface {
+    texture: Arc<Texture>,
+    sampler: Sampler,
+    texcoords: Vec<glm::Vec2>,
+    triangle_index_buffer: Vec<u32>,
+}
+
 pub struct Model {
+    index: usize,
     name: String,
     color: Color,
+    vertex_colors: Option<Vec<Color>>,
+    color_override: Option<Color>,
     vertex_buffer_view: BufferView,
     index_buffer: Vec<u32>,
+    surface: Option<Surface>,
+    // Bounds of the node's own mesh before its transform, from the position accessor's min/max
+    mesh_bounds: Aabb,
     translation: glm::Vec3,
     rotation: glm::Quat,
     scale: glm::Vec3,
@@ -60,79 +79,174 @@ pub struct Model {
 }
 
 impl Model {
-    pub fn from(gltf: &gltf::Document, buffer: &[u8], color: Color) -> Self {
-        let scene = gltf.default_scene().unwrap();
-        let node = scene.nodes().nth(0).unwrap();
-        Self::from_node(&node, buffer, color)
+    pub fn from(
+        gltf: &gltf::Document,
+        buffers: &[Vec<u8>],
+        textures: &[Arc<Texture>],
+        color: Option<Color>,
+    ) -> Result<Self, JsValue> {
+        let scene = gltf.default_scene().ok_or("Default scene not found")?;
+        let node = scene.nodes().nth(0).ok_or("Default scene is empty")?;
+        Self::from_node(&node, buffers, textures, color)
     }
 
-    pub fn from_node(node: &gltf::Node, buffer: &[u8], color: Color) -> Self {
-        let name = node.name().unwrap().to_string();
-        if node.mesh().unwrap().primitives().len() != 1 {
-            panic!("Multiple primitives not supported!");
+    pub fn from_node(
+        node: &gltf::Node,
+        buffers: &[Vec<u8>],
+        textures: &[Arc<Texture>],
+        color: Option<Color>,
+    ) -> Result<Self, JsValue> {
+        let name = node
+            .name()
+            .ok_or(format!("Node {} has no name", node.index()))?
+            .to_string();
+        let mesh = node.mesh().ok_or(format!("Node {} has no mesh", name))?;
+        if mesh.primitives().len() != 1 {
+            return Err(format!("Mesh of node {} must have exactly one primitive", name).into());
         }
-        let primitive = node.mesh().unwrap().primitives().nth(0).unwrap();
-        let vertex_view = primitive
+        let primitive = mesh.primitives().nth(0).unwrap();
+        let vertex_accessor = primitive
             .attributes()
             .find_map(|attribute| match attribute.0 {
                 gltf::Semantic::Positions => Some(attribute.1),
                 _ => None,
             })
-            .expect("Position attribute not found!")
+            .ok_or(format!("Position attribute of node {} not found", name))?;
+        if vertex_accessor.data_type() != ComponentType::F32
+            || vertex_accessor.dimensions() != gltf::accessor::Dimensions::Vec3
+        {
+            return Err(format!("Positions of node {} must be float vectors", name).into());
+        }
+        let mesh_bounds = Aabb::from_bounds(&primitive.bounding_box());
+        let vertex_view = vertex_accessor
             .view()
-            .unwrap();
+            .ok_or(format!("Positions of node {} are sparse", name))?;
+        // Positions are read straight out of the buffer, so they have to be tightly packed
+        if vertex_view
+            .stride()
+            .is_some_and(|stride| stride != size_of::<glm::Vec3>())
+        {
+            return Err(format!("Positions of node {} are interleaved", name).into());
+        }
         let vertex_buffer_view = BufferView {
-            offset: vertex_view.offset(),
-            length: vertex_view.length(),
+            buffer: vertex_view.buffer().index(),
+            offset: vertex_view.offset() + vertex_accessor.offset(),
+            length: vertex_accessor.count() * size_of::<glm::Vec3>(),
         };
-        let index_accessor = primitive.indices().unwrap();
-        let index_view = index_accessor.view().unwrap();
-        let index_buffer = match index_accessor.data_type() {
-            ComponentType::U8 => {
-                let index_buffer = buffer
-                    [index_view.offset()..(index_view.offset() + index_view.length())]
-                    .into_iter()
-                    .map(|&index| index as u32)
-                    .collect_vec();
-                triangles_to_lines_index(&index_buffer)
-            }
-            ComponentType::U16 => {
-                let index_buffer = bytemuck::cast_slice::<u8, u16>(
-                    &buffer[index_view.offset()..(index_view.offset() + index_view.length())],
-                )
+        if vertex_buffer_view.offset + vertex_buffer_view.length
+            > buffers[vertex_buffer_view.buffer].len()
+        {
+            return Err(format!("Positions of node {} run past their buffer", name).into());
+        }
+        let index_accessor = primitive
+            .indices()
+            .ok_or(format!("Mesh of node {} isn't indexed", name))?;
+        let index_view = index_accessor
+            .view()
+            .ok_or(format!("Indices of node {} are sparse", name))?;
+        let buffer = &buffers[index_view.buffer().index()];
+        let index_offset = index_view.offset() + index_accessor.offset();
+        let index_length = index_accessor.count() * index_accessor.size();
+        if index_offset + index_length > buffer.len() {
+            return Err(format!("Indices of node {} run past their buffer", name).into());
+        }
+        let triangle_index_buffer = match index_accessor.data_type() {
+            ComponentType::U8 => buffer[index_offset..(index_offset + index_length)]
                 .into_iter()
                 .map(|&index| index as u32)
-                .collect_vec();
-                triangles_to_lines_index(&index_buffer)
+                .collect_vec(),
+            ComponentType::U16 => bytemuck::cast_slice::<u8, u16>(
+                &buffer[index_offset..(index_offset + index_length)],
+            )
+            .into_iter()
+            .map(|&index| index as u32)
+            .collect_vec(),
+            ComponentType::U32 => {
+                bytemuck::cast_slice(&buffer[index_offset..(index_offset + index_length)]).to_vec()
             }
-            ComponentType::U32 => triangles_to_lines_index(bytemuck::cast_slice(
-                &buffer[index_view.offset()..(index_view.offset() + index_view.length())],
-            )),
-            _ => panic!("Unsupported index accessor data type!"),
+            _ => return Err(format!("Indices of node {} have an unsupported type", name).into()),
         };
+        let index_buffer = triangles_to_lines_index(&triangle_index_buffer);
+        let base_color = primitive
+            .material()
+            .pbr_metallic_roughness()
+            .base_color_factor();
+        let reader = primitive.reader(|buffer| Some(buffers[buffer.index()].as_slice()));
+        let surface = primitive
+            .material()
+            .pbr_metallic_roughness()
+            .base_color_texture()
+            .and_then(|info| {
+                let texcoords = reader
+                    .read_tex_coords(info.tex_coord())?
+                    .into_f32()
+                    .map(|texcoord| glm::make_vec2(&texcoord))
+                    .collect_vec();
+                let filter = match info.texture().sampler().mag_filter() {
+                    Some(gltf::texture::MagFilter::Nearest) => Filter::Nearest,
+                    _ => Filter::Bilinear,
+                };
+                let sampler = info.texture().sampler();
+                let wrap = [sampler.wrap_s(), sampler.wrap_t()].map(|mode| match mode {
+                    gltf::texture::WrappingMode::Repeat => Wrap::Repeat,
+                    gltf::texture::WrappingMode::MirroredRepeat => Wrap::MirroredRepeat,
+                    gltf::texture::WrappingMode::ClampToEdge => Wrap::ClampToEdge,
+                });
+                Some(Surface {
+                    texture: textures[info.texture().source().index()].clone(),
+                    sampler: Sampler {
+                        filter,
+                        wrap,
+                        tint: Color::from_linear([base_color[0], base_color[1], base_color[2]]),
+                    },
+                    texcoords,
+                    triangle_index_buffer,
+                })
+            });
+        let vertex_colors = reader.read_colors(0).map(|colors| {
+            colors
+                .into_rgb_f32()
+                .map(|color| {
+                    Color::from_linear([
+                        color[0] * base_color[0],
+                        color[1] * base_color[1],
+                        color[2] * base_color[2],
+                    ])
+                })
+                .collect_vec()
+        });
         let (translation, rotation, scale) = node.transform().decomposed();
         let translation = glm::make_vec3(&translation);
         let rotation = glm::make_quat(&rotation);
         let scale = glm::make_vec3(&scale);
         let children = node
             .children()
-            .map(|child| Model::from_node(&child, buffer, color))
-            .collect_vec();
+            .map(|child| Model::from_node(&child, buffers, textures, color))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        Self {
+        Ok(Self {
+            index: node.index(),
             name,
-            color,
+            color: Color::from_linear([base_color[0], base_color[1], base_color[2]]),
+            vertex_colors,
+            color_override: color,
             vertex_buffer_view,
             index_buffer,
+            surface,
+            mesh_bounds,
             translation,
             rotation,
             scale,
             children,
-        }
+        })
     }
 
     pub fn set_color(&mut self, color: Color) {
-        self.color = color;
+        self.color_override = Some(color);
+    }
+
+    pub fn reset_color(&mut self) {
+        self.color_override = None;
     }
 
     pub fn set_child_color(&mut self, name: &str, color: Color) {
@@ -144,6 +258,15 @@ impl Model {
         }
     }
 
+    pub fn find_node_mut(&mut self, index: usize) -> Option<&mut Model> {
+        if self.index == index {
+            return Some(self);
+        }
+        self.children
+            .iter_mut()
+            .find_map(|child| child.find_node_mut(index))
+    }
+
     pub fn set_translation(&mut self, translation: glm::Vec3) {
         self.translation = translation;
     }
@@ -168,23 +291,58 @@ impl Model {
         self.scale
     }
 
+    fn line_color(&self) -> gfx::LineColor<'_> {
+        match (self.color_override, &self.vertex_colors) {
+            (Some(color), _) => gfx::LineColor::Uniform(color),
+            (None, Some(colors)) => gfx::LineColor::PerVertex(colors),
+            (None, None) => gfx::LineColor::Uniform(self.color),
+        }
+    }
+
     pub fn model_matrix(&self) -> glm::Mat4 {
         glm::translation(&self.translation)
             * glm::quat_to_mat4(&self.rotation)
             * glm::scaling(&self.scale)
     }
 
-    pub fn draw(&self, framebuffer: &mut gfx::Framebuffer, camera: &glm::Mat4, buffer: &[u8]) {
+    // Bounds of the whole node tree in the parent's space, follows animated transforms
+    pub fn bounds(&self) -> Aabb {
+        self.children
+            .iter()
+            .fold(self.mesh_bounds, |bounds, child| {
+                bounds.union(&child.bounds())
+            })
+            .transformed(&self.model_matrix())
+    }
+
+    pub fn draw(
+        &self,
+        framebuffer: &mut gfx::Framebuffer,
+        camera: &glm::Mat4,
+        buffers: &[Vec<u8>],
+    ) {
         let transform = camera * self.model_matrix();
-        gfx::draw_line_list_indexed(
-            framebuffer,
-            self.vertex_buffer_view.look(buffer),
-            &self.index_buffer,
-            &transform,
-            self.color,
-        );
+        let vertexes = self.vertex_buffer_view.look(buffers);
+        match (&self.surface, self.color_override) {
+            (Some(surface), None) => gfx::draw_triangle_list_indexed(
+                framebuffer,
+                vertexes,
+                &surface.texcoords,
+                &surface.triangle_index_buffer,
+                &transform,
+                &surface.texture,
+                surface.sampler,
+            ),
+            _ => gfx::draw_line_list_indexed(
+                framebuffer,
+                vertexes,
+                &self.index_buffer,
+                &transform,
+                self.line_color(),
+            ),
+        }
         for child in &self.children {
-            child.draw(framebuffer, &transform, buffer);
+            child.draw(framebuffer, &transform, buffers);
         }
     }
 }
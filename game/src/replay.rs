@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rules::GameRules;
+
+// Bumped whenever a change to the simulation makes older replays play back differently
+pub const REPLAY_VERSION: u32 = 1;
+const MAX_LANES: usize = 8;
+const MAX_OBSTACLES: usize = 100;
+
+// Everything that steers the simulation, pausing doesn't and isn't recorded
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameInput {
+    Left,
+    Right,
+    Lane(usize),
+}
+
+// A run is the seed and rules it started with plus the inputs that steered it, replaying them
+// through the same fixed steps ends up in exactly the same run
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u32,
+    pub rules: GameRules,
+    // Inputs with the simulation step they were applied on
+    pub events: Vec<(u32, GameInput)>,
+    pub frames: u32,
+    pub score: u32,
+}
+
+impl Replay {
+    pub fn new(seed: u32, rules: GameRules) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            rules,
+            events: Vec::new(),
+            frames: 0,
+            score: 0,
+        }
+    }
+
+    pub fn record(&mut self, frame: u32, input: GameInput) {
+        self.events.push((frame, input));
+    }
+
+    pub fn finish(&mut self, frames: u32, score: u32) {
+        self.frames = frames;
+        self.score = score;
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let replay: Replay =
+            serde_json::from_str(json).map_err(|error| format!("Invalid replay: {}", error))?;
+        if replay.version != REPLAY_VERSION {
+            return Err("Replay was recorded by a different version of the game".to_string());
+        }
+        let rules = &replay.rules;
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        if !(1..=MAX_LANES).contains(&rules.lane_count)
+            || rules.obstacle_count > MAX_OBSTACLES
+            || !positive(rules.lane_spacing)
+            || !positive(rules.spawn_spacing.start)
+            || !positive(rules.spawn_spacing.end)
+            || rules.spawn_spacing.is_empty()
+            || !positive(rules.start_speed)
+            || !positive(rules.max_speed)
+            || !rules.speed_increase.is_finite()
+            || rules.speed_increase < 0.0
+            || !positive(rules.player_lerp_rate)
+        {
+            return Err("Replay has invalid rules".to_string());
+        }
+        Ok(replay)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
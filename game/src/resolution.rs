@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+const TARGET_FPS: f32 = 60.0;
+const MIN_SCALE: f32 = 0.25;
+const MAX_SCALE: f32 = 1.0;
+const SCALE_STEP: f32 = 0.05;
+// Seconds between adjustments so a resize has time to show up in the average
+const ADJUSTMENT_INTERVAL: f32 = 0.5;
+
+// Scales the framebuffer relative to the canvas to keep the time spent rendering within the frame budget
+pub struct ResolutionScaler {
+    scale: f32,
+    average_frame_time: f32,
+    since_adjustment: f32,
+}
+
+impl ResolutionScaler {
+    pub fn new() -> Self {
+        Self {
+            scale: MAX_SCALE,
+            average_frame_time: 0.0,
+            since_adjustment: 0.0,
+        }
+    }
+
+    pub fn scaled_size(&self, width: f64, height: f64) -> (usize, usize) {
+        let scale = self.scale as f64;
+        (
+            ((width * scale) as usize).max(1),
+            ((height * scale) as usize).max(1),
+        )
+    }
+
+    // Takes the time spent rendering the last frame, returns true when the scale changed
+    pub fn update(&mut self, frame_time: Duration, delta_time: Duration) -> bool {
+        let frame_time = frame_time.as_secs_f32();
+        self.average_frame_time = self.average_frame_time * 0.9 + frame_time * 0.1;
+        self.since_adjustment += delta_time.as_secs_f32();
+        if self.since_adjustment < ADJUSTMENT_INTERVAL {
+            return false;
+        }
+
+        let budget = 1.0 / TARGET_FPS;
+        let scale = if self.average_frame_time > budget * 0.9 {
+            (self.scale - SCALE_STEP).max(MIN_SCALE)
+        } else if self.average_frame_time < budget * 0.6 {
+            (self.scale + SCALE_STEP).min(MAX_SCALE)
+        } else {
+            self.scale
+        };
+        self.since_adjustment = 0.0;
+        if scale == self.scale {
+            return false;
+        }
+        self.scale = scale;
+        true
+    }
+}
@@ -0,0 +1,132 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    // Also the name the server files runs under
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+}
+
+// Lanes are laid out side by side, centred on x = 0
+#[derive(Copy, Clone, PartialEq)]
+pub struct Lanes {
+    count: usize,
+    spacing: f32,
+}
+
+impl Lanes {
+    pub fn new(count: usize, spacing: f32) -> Self {
+        assert!(count > 0, "At least one lane is needed!");
+        Self { count, spacing }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn spacing(&self) -> f32 {
+        self.spacing
+    }
+
+    pub fn x(&self, lane: usize) -> f32 {
+        (lane as f32 - (self.count - 1) as f32 / 2.0) * self.spacing
+    }
+
+    pub fn middle(&self) -> usize {
+        self.count / 2
+    }
+
+    // Lanes wrap around, moving left from the leftmost lane ends up in the rightmost one
+    pub fn offset(&self, lane: usize, offset: isize) -> usize {
+        (lane as isize + offset).rem_euclid(self.count as isize) as usize
+    }
+
+    // Distance from the centre to the outer edge of the outermost lanes
+    pub fn half_width(&self, object_width: f32) -> f32 {
+        self.x(self.count - 1) + object_width / 2.0
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameRules {
+    pub difficulty: Difficulty,
+    pub lane_count: usize,
+    // Distance between the centres of neighbouring lanes
+    pub lane_spacing: f32,
+    pub obstacle_count: usize,
+    // Distance between consecutive obstacles when the track is laid out
+    pub spawn_spacing: Range<f32>,
+    pub start_speed: f32,
+    // Added every time an obstacle is passed, up to max_speed
+    pub speed_increase: f32,
+    pub max_speed: f32,
+    // How quickly the player slides over to the lane it's moving to
+    pub player_lerp_rate: f32,
+}
+
+impl GameRules {
+    pub fn lanes(&self) -> Lanes {
+        Lanes::new(self.lane_count, self.lane_spacing)
+    }
+
+    pub fn preset(difficulty: Difficulty) -> Self {
+        match difficulty {
+            Difficulty::Easy => Self {
+                difficulty,
+                lane_count: 4,
+                lane_spacing: 1.1,
+                obstacle_count: 10,
+                spawn_spacing: 8.0..12.0,
+                start_speed: 1.5,
+                speed_increase: 0.05,
+                max_speed: 8.0,
+                player_lerp_rate: 7.0,
+            },
+            Difficulty::Normal => Self {
+                difficulty,
+                lane_count: 3,
+                lane_spacing: 1.1,
+                obstacle_count: 15,
+                spawn_spacing: 6.0..10.0,
+                start_speed: 2.0,
+                speed_increase: 0.1,
+                max_speed: 12.0,
+                player_lerp_rate: 5.0,
+            },
+            Difficulty::Hard => Self {
+                difficulty,
+                lane_count: 3,
+                lane_spacing: 1.1,
+                obstacle_count: 20,
+                spawn_spacing: 4.5..8.0,
+                start_speed: 3.0,
+                speed_increase: 0.15,
+                max_speed: 16.0,
+                player_lerp_rate: 4.0,
+            },
+        }
+    }
+}
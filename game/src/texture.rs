@@ -0,0 +1,104 @@
+use nalgebra_glm as glm;
+
+use crate::Color;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+// What happens to texture coordinates outside 0..1
+#[derive(Copy, Clone, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+// How a surface reads its texture, as given by the glTF sampler
+#[derive(Copy, Clone, PartialEq)]
+pub struct Sampler {
+    pub filter: Filter,
+    // Horizontal and vertical
+    pub wrap: [Wrap; 2],
+    // The material's base color factor, multiplied into every sample
+    pub tint: Color,
+}
+
+impl Wrap {
+    #[inline]
+    fn apply(self, coordinate: i64, size: usize) -> usize {
+        let size = size as i64;
+        match self {
+            Wrap::Repeat => coordinate.rem_euclid(size) as usize,
+            Wrap::MirroredRepeat => {
+                let coordinate = coordinate.rem_euclid(size * 2);
+                if coordinate < size {
+                    coordinate as usize
+                } else {
+                    (size * 2 - 1 - coordinate) as usize
+                }
+            }
+            Wrap::ClampToEdge => coordinate.clamp(0, size - 1) as usize,
+        }
+    }
+}
+
+pub struct Texture {
+    texels: Vec<Color>,
+    width: usize,
+    height: usize,
+}
+
+impl Texture {
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        let image = image::load_from_memory(data)
+            .map_err(|error| error.to_string())?
+            .to_rgb8();
+        let width = image.width() as usize;
+        let height = image.height() as usize;
+        Ok(Self {
+            texels: bytemuck::cast_slice(image.as_raw()).to_vec(),
+            width,
+            height,
+        })
+    }
+
+    pub fn sample(&self, uv: glm::Vec2, sampler: Sampler) -> Color {
+        let wrap = sampler.wrap;
+        let x = uv[0] * self.width as f32;
+        let y = uv[1] * self.height as f32;
+        let color = match sampler.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64, wrap),
+            Filter::Bilinear => {
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let tx = x - x0;
+                let ty = y - y0;
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = self
+                    .texel(x0, y0, wrap)
+                    .lerp(self.texel(x0 + 1, y0, wrap), tx);
+                let bottom = self
+                    .texel(x0, y0 + 1, wrap)
+                    .lerp(self.texel(x0 + 1, y0 + 1, wrap), tx);
+                top.lerp(bottom, ty)
+            }
+        };
+        if sampler.tint == Color::WHITE {
+            color
+        } else {
+            color.modulate(sampler.tint)
+        }
+    }
+
+    #[inline]
+    fn texel(&self, x: i64, y: i64, wrap: [Wrap; 2]) -> Color {
+        let x = wrap[0].apply(x, self.width);
+        let y = wrap[1].apply(y, self.height);
+        self.texels[self.width * y + x]
+    }
+}
@@ -1,7 +1,10 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use itertools::Itertools;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{
-    WebGlBuffer, WebGlProgram, WebGlRenderingContext, WebGlShader, WebGlTexture,
+    WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlRenderingContext, WebGlShader, WebGlTexture,
     WebGlUniformLocation,
 };
 
@@ -11,13 +14,189 @@ extern "C" {
     pub fn log(s: &str);
 }
 
+#[derive(Copy, Clone, PartialEq)]
+pub enum Effect {
+    Bloom,
+    ColorGrading,
+    Scanlines,
+    Vignette,
+}
+
+// Bit set of enabled effects, shared with the page through set_effect_enabled
+static ENABLED_EFFECTS: AtomicU32 = AtomicU32::new(Effect::Bloom.bit() | Effect::Vignette.bit());
+
+impl Effect {
+    // In the order they are applied
+    pub const ALL: [Effect; 4] = [
+        Effect::Bloom,
+        Effect::ColorGrading,
+        Effect::Scanlines,
+        Effect::Vignette,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|effect| effect.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Effect::Bloom => "bloom",
+            Effect::ColorGrading => "color-grading",
+            Effect::Scanlines => "scanlines",
+            Effect::Vignette => "vignette",
+        }
+    }
+
+    const fn bit(self) -> u32 {
+        1 << self as u32
+    }
+
+    pub fn is_enabled(self) -> bool {
+        ENABLED_EFFECTS.load(Ordering::Relaxed) & self.bit() != 0
+    }
+
+    pub fn set_enabled(self, enabled: bool) {
+        if enabled {
+            ENABLED_EFFECTS.fetch_or(self.bit(), Ordering::Relaxed);
+        } else {
+            ENABLED_EFFECTS.fetch_and(!self.bit(), Ordering::Relaxed);
+        }
+    }
+
+    fn fragment_shader_source(self) -> &'static str {
+        match self {
+            Effect::Bloom => {
+                r#"
+precision mediump float;
+
+uniform sampler2D sampler;
+uniform vec2 resolution;
+
+varying vec2 texCoord;
+
+void main() {
+    vec3 color = texture2D(sampler, texCoord).rgb;
+    vec3 glow = vec3(0.0);
+    float total = 0.0;
+    for (int x = -3; x <= 3; x++) {
+        for (int y = -3; y <= 3; y++) {
+            vec2 offset = vec2(float(x), float(y)) * 2.0 / resolution;
+            float weight = exp(-float(x * x + y * y) / 8.0);
+            vec3 texel = texture2D(sampler, texCoord + offset).rgb;
+            glow += max(texel - 0.3, 0.0) * weight;
+            total += weight;
+        }
+    }
+    gl_FragColor = vec4(color + glow / total * 3.0, 1.0);
+}
+"#
+            }
+            Effect::ColorGrading => {
+                r#"
+precision mediump float;
+
+uniform sampler2D sampler;
+
+varying vec2 texCoord;
+
+void main() {
+    vec3 color = texture2D(sampler, texCoord).rgb;
+    color = (color - 0.5) * 1.15 + 0.5;
+    float luma = dot(color, vec3(0.299, 0.587, 0.114));
+    color = mix(vec3(luma), color, 1.2) * vec3(1.05, 0.95, 1.1);
+    gl_FragColor = vec4(clamp(color, 0.0, 1.0), 1.0);
+}
+"#
+            }
+            Effect::Scanlines => {
+                r#"
+precision mediump float;
+
+uniform sampler2D sampler;
+uniform vec2 resolution;
+
+varying vec2 texCoord;
+
+void main() {
+    vec3 color = texture2D(sampler, texCoord).rgb;
+    float scanline = 0.75 + 0.25 * sin(texCoord.y * resolution.y * 2.0944);
+    gl_FragColor = vec4(color * scanline, 1.0);
+}
+"#
+            }
+            Effect::Vignette => {
+                r#"
+precision mediump float;
+
+uniform sampler2D sampler;
+
+varying vec2 texCoord;
+
+void main() {
+    vec3 color = texture2D(sampler, texCoord).rgb;
+    float vignette = smoothstep(0.8, 0.25, length(texCoord - 0.5));
+    gl_FragColor = vec4(color * mix(0.35, 1.0, vignette), 1.0);
+}
+"#
+            }
+        }
+    }
+}
+
+#[wasm_bindgen(js_name = setEffectEnabled)]
+pub fn set_effect_enabled(name: &str, enabled: bool) -> Result<(), JsValue> {
+    Effect::from_name(name)
+        .ok_or_else(|| format!("Unknown effect {}", name))?
+        .set_enabled(enabled);
+    Ok(())
+}
+
+struct Pass {
+    program: WebGlProgram,
+    sampler_uniform: Option<WebGlUniformLocation>,
+    resolution_uniform: Option<WebGlUniformLocation>,
+    position_attrib_loc: i32,
+}
+
+impl Pass {
+    fn new(
+        context: &WebGlRenderingContext,
+        vertex_shader: &WebGlShader,
+        fragment_shader_source: &str,
+    ) -> Result<Self, JsValue> {
+        let fragment_shader = WebGLContext::compile_shader(
+            context,
+            WebGlRenderingContext::FRAGMENT_SHADER,
+            fragment_shader_source,
+        )?;
+        let program = WebGLContext::link_program(context, vertex_shader, &fragment_shader)?;
+        Ok(Self {
+            sampler_uniform: context.get_uniform_location(&program, "sampler"),
+            resolution_uniform: context.get_uniform_location(&program, "resolution"),
+            position_attrib_loc: context.get_attrib_location(&program, "position"),
+            program,
+        })
+    }
+}
+
+// Intermediate texture the passes before the last one render into
+struct RenderTarget {
+    framebuffer: WebGlFramebuffer,
+    texture: WebGlTexture,
+}
+
 pub struct WebGLContext {
     context: WebGlRenderingContext,
-    program: WebGlProgram,
+    blit_pass: Pass,
+    effect_passes: Vec<Pass>,
+    render_targets: [RenderTarget; 2],
     texture: WebGlTexture,
-    sampler_uniform: WebGlUniformLocation,
-    position_attrib_loc: i32,
     vertex_buffer: WebGlBuffer,
+    width: i32,
+    height: i32,
 }
 
 impl WebGLContext {
@@ -62,21 +241,11 @@ void main() {
             Self::VERTEX_SHADER_SOURCE,
         )?;
 
-        let fragment_shader = Self::compile_shader(
-            &context,
-            WebGlRenderingContext::FRAGMENT_SHADER,
-            Self::FRAGMENT_SHADER_SOURCE,
-        )?;
-
-        let program = Self::link_program(&context, &vertex_shader, &fragment_shader)?;
-        context.use_program(Some(&program));
-        let position_attrib_loc = context.get_attrib_location(&program, "position");
-
-        let sampler_uniform = context
-            .get_uniform_location(&program, "sampler")
-            .ok_or("Unable to locate uniform")?;
-
-        context.uniform1i(Some(&sampler_uniform), 0);
+        let blit_pass = Pass::new(&context, &vertex_shader, Self::FRAGMENT_SHADER_SOURCE)?;
+        let effect_passes = Effect::ALL
+            .iter()
+            .map(|effect| Pass::new(&context, &vertex_shader, effect.fragment_shader_source()))
+            .collect::<Result<Vec<_>, _>>()?;
 
         let vertex_buffer = context.create_buffer().ok_or("Unable to create buffer")?;
         context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
@@ -90,17 +259,32 @@ void main() {
             );
         };
 
-        context.enable_vertex_attrib_array(position_attrib_loc as u32);
-        context.vertex_attrib_pointer_with_i32(
-            position_attrib_loc as u32,
-            2,
-            WebGlRenderingContext::FLOAT,
-            false,
-            0,
-            0,
-        );
-
         context.active_texture(WebGlRenderingContext::TEXTURE0);
+        // The framebuffer is smaller than the canvas when the resolution is scaled down
+        let texture = Self::create_texture(&context, WebGlRenderingContext::LINEAR)?;
+        let render_targets = [
+            Self::create_render_target(&context)?,
+            Self::create_render_target(&context)?,
+        ];
+
+        let mut web_gl_context = Self {
+            context,
+            blit_pass,
+            effect_passes,
+            render_targets,
+            texture,
+            vertex_buffer,
+            width: 0,
+            height: 0,
+        };
+        web_gl_context.resize(canvas.width() as i32, canvas.height() as i32)?;
+        Ok(web_gl_context)
+    }
+
+    fn create_texture(
+        context: &WebGlRenderingContext,
+        filter: u32,
+    ) -> Result<WebGlTexture, JsValue> {
         let texture = context.create_texture().ok_or("Unable to create texture")?;
         context.bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&texture));
         context.tex_parameteri(
@@ -116,25 +300,30 @@ void main() {
         context.tex_parameteri(
             WebGlRenderingContext::TEXTURE_2D,
             WebGlRenderingContext::TEXTURE_MIN_FILTER,
-            WebGlRenderingContext::NEAREST as i32,
+            filter as i32,
         );
         context.tex_parameteri(
             WebGlRenderingContext::TEXTURE_2D,
             WebGlRenderingContext::TEXTURE_MAG_FILTER,
-            WebGlRenderingContext::NEAREST as i32,
+            filter as i32,
         );
+        Ok(texture)
+    }
 
-        Ok(Self {
-            context,
-            program,
+    fn create_render_target(context: &WebGlRenderingContext) -> Result<RenderTarget, JsValue> {
+        let texture = Self::create_texture(context, WebGlRenderingContext::NEAREST)?;
+        let framebuffer = context
+            .create_framebuffer()
+            .ok_or("Unable to create framebuffer")?;
+        Ok(RenderTarget {
+            framebuffer,
             texture,
-            sampler_uniform,
-            position_attrib_loc,
-            vertex_buffer,
         })
     }
 
     pub fn update_texture(&self, data: &[u8], width: i32, height: i32) -> Result<(), JsValue> {
+        self.context
+            .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&self.texture));
         self.context
             .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
                 WebGlRenderingContext::TEXTURE_2D,
@@ -149,21 +338,43 @@ void main() {
             )
     }
 
-    pub fn resize(&self, width: i32, height: i32) {
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), JsValue> {
+        self.width = width;
+        self.height = height;
         self.context.viewport(0, 0, width, height);
+        for target in &self.render_targets {
+            self.context
+                .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&target.texture));
+            self.context
+                .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                    WebGlRenderingContext::TEXTURE_2D,
+                    0,
+                    WebGlRenderingContext::RGBA as i32,
+                    width,
+                    height,
+                    0,
+                    WebGlRenderingContext::RGBA,
+                    WebGlRenderingContext::UNSIGNED_BYTE,
+                    None,
+                )?;
+            self.context.bind_framebuffer(
+                WebGlRenderingContext::FRAMEBUFFER,
+                Some(&target.framebuffer),
+            );
+            self.context.framebuffer_texture_2d(
+                WebGlRenderingContext::FRAMEBUFFER,
+                WebGlRenderingContext::COLOR_ATTACHMENT0,
+                WebGlRenderingContext::TEXTURE_2D,
+                Some(&target.texture),
+                0,
+            );
+        }
+        self.context
+            .bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, None);
+        Ok(())
     }
 
     pub fn bind_all_objects(&self) {
-        self.context
-            .enable_vertex_attrib_array(self.position_attrib_loc as u32);
-        self.context.vertex_attrib_pointer_with_i32(
-            self.position_attrib_loc as u32,
-            2,
-            WebGlRenderingContext::FLOAT,
-            false,
-            0,
-            0,
-        );
         self.context.bind_buffer(
             WebGlRenderingContext::ARRAY_BUFFER,
             Some(&self.vertex_buffer),
@@ -171,13 +382,53 @@ void main() {
         self.context.active_texture(WebGlRenderingContext::TEXTURE0);
         self.context
             .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&self.texture));
-        self.context.use_program(Some(&self.program));
-        self.context.uniform1i(Some(&self.sampler_uniform), 0);
     }
 
+    // Runs the enabled effects in order, each one reading the output of the previous one
     pub fn draw(&self) {
-        self.context
-            .draw_arrays(WebGlRenderingContext::TRIANGLE_STRIP, 0, 4);
+        let mut passes = Effect::ALL
+            .iter()
+            .zip(&self.effect_passes)
+            .filter(|(effect, _)| effect.is_enabled())
+            .map(|(_, pass)| pass)
+            .collect_vec();
+        if passes.is_empty() {
+            passes.push(&self.blit_pass);
+        }
+
+        let mut source = &self.texture;
+        for (index, pass) in passes.iter().enumerate() {
+            let target = &self.render_targets[index % 2];
+            let framebuffer = if index + 1 == passes.len() {
+                None
+            } else {
+                Some(&target.framebuffer)
+            };
+            self.context
+                .bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, framebuffer);
+            self.context
+                .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(source));
+            self.context.use_program(Some(&pass.program));
+            self.context.uniform1i(pass.sampler_uniform.as_ref(), 0);
+            self.context.uniform2f(
+                pass.resolution_uniform.as_ref(),
+                self.width as f32,
+                self.height as f32,
+            );
+            self.context
+                .enable_vertex_attrib_array(pass.position_attrib_loc as u32);
+            self.context.vertex_attrib_pointer_with_i32(
+                pass.position_attrib_loc as u32,
+                2,
+                WebGlRenderingContext::FLOAT,
+                false,
+                0,
+                0,
+            );
+            self.context
+                .draw_arrays(WebGlRenderingContext::TRIANGLE_STRIP, 0, 4);
+            source = &target.texture;
+        }
     }
 
     // Adapted from https://github.com/rustwasm/wasm-bindgen/tree/master/examples/webgl
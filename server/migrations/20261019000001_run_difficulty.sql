@@ -0,0 +1,5 @@
+-- Runs are ranked separately for each of the game's rule presets
+ALTER TABLE runs
+    ADD COLUMN difficulty VARCHAR(10) NOT NULL DEFAULT 'normal' AFTER score;
+
+CREATE INDEX runs_difficulty_score ON runs (difficulty, score);
@@ -0,0 +1,6 @@
+-- Inputs recorded by the game, watched from the Hall of Fame
+CREATE TABLE replays (
+    run_id INT UNSIGNED NOT NULL PRIMARY KEY,
+    data MEDIUMTEXT NOT NULL,
+    FOREIGN KEY (run_id) REFERENCES runs (id) ON DELETE CASCADE
+);
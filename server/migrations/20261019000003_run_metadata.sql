@@ -0,0 +1,7 @@
+-- Reported by the game with each run, NULL for runs submitted before it was
+ALTER TABLE runs
+    ADD COLUMN duration FLOAT NULL,
+    ADD COLUMN obstacles_passed INT UNSIGNED NULL,
+    ADD COLUMN max_speed FLOAT NULL,
+    ADD COLUMN rules TEXT NULL,
+    ADD COLUMN version VARCHAR(40) NULL;
@@ -0,0 +1,9 @@
+-- Nonces of run tickets that were already redeemed, a ticket can submit a single run
+CREATE TABLE used_run_tickets (
+    nonce CHAR(32) NOT NULL PRIMARY KEY,
+    user_id INT UNSIGNED NOT NULL,
+    used_at DATETIME NOT NULL
+);
+
+-- Nonces of expired tickets are pruned by when they were used
+CREATE INDEX used_run_tickets_used_at ON used_run_tickets (used_at);
@@ -0,0 +1,19 @@
+-- Players can be promoted to moderators, who hide runs and ban players, and admins, who also
+-- delete runs and read the audit log
+ALTER TABLE users
+    ADD COLUMN role VARCHAR(10) NOT NULL DEFAULT 'player',
+    ADD COLUMN banned TINYINT(1) NOT NULL DEFAULT 0;
+
+-- Hidden runs stay in the database but are left out of the leaderboards
+ALTER TABLE runs ADD COLUMN hidden TINYINT(1) NOT NULL DEFAULT 0;
+
+-- Every moderation action, kept when the moderator's account is deleted
+CREATE TABLE audit_log (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    actor_id INT UNSIGNED,
+    action VARCHAR(20) NOT NULL,
+    target_kind VARCHAR(10) NOT NULL,
+    target_id INT UNSIGNED NOT NULL,
+    time DATETIME NOT NULL,
+    FOREIGN KEY (actor_id) REFERENCES users(id) ON DELETE SET NULL
+);
@@ -0,0 +1,18 @@
+-- Suspended players can't log in until the time passes
+ALTER TABLE users ADD COLUMN suspended_until DATETIME;
+
+-- Reports of a message, run or user, open until a moderator resolves them
+CREATE TABLE reports (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    reporter_id INT UNSIGNED NOT NULL,
+    target_kind VARCHAR(10) NOT NULL,
+    target_id INT UNSIGNED NOT NULL,
+    reason VARCHAR(500) NOT NULL,
+    time DATETIME NOT NULL,
+    resolution VARCHAR(10),
+    resolver_id INT UNSIGNED,
+    resolved_at DATETIME,
+    INDEX (resolution),
+    FOREIGN KEY (reporter_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (resolver_id) REFERENCES users(id) ON DELETE SET NULL
+);
@@ -0,0 +1,16 @@
+-- Usernames are up to 20 grapheme clusters of up to 8 code points each
+ALTER TABLE users MODIFY username VARCHAR(160) NOT NULL;
+
+-- Lowercased NFKC form of the username, compared byte for byte so the server alone decides which
+-- names count as the same. Existing names get their key once here, LOWER matches what the server
+-- computes for them as long as they don't use compatibility forms like fullwidth letters, and
+-- names that only differ in case have to be renamed before the unique index can be added.
+ALTER TABLE users ADD COLUMN username_key VARCHAR(160) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin;
+UPDATE users SET username_key = LOWER(username);
+ALTER TABLE users
+    MODIFY username_key VARCHAR(160) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+    ADD UNIQUE INDEX (username_key);
+
+-- Message and report lengths are counted in grapheme clusters, which can take several characters
+ALTER TABLE messages MODIFY text TEXT NOT NULL;
+ALTER TABLE reports MODIFY reason TEXT NOT NULL;
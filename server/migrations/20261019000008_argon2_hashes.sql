@@ -0,0 +1,2 @@
+-- Argon2id hashes in PHC string format are longer than the 60 characters of bcrypt hashes
+ALTER TABLE users MODIFY password_hash VARCHAR(255) NOT NULL;
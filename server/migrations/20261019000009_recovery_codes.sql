@@ -0,0 +1,8 @@
+-- HMAC-SHA256 hashes of the unused recovery codes, one row each so redeeming one is a single
+-- lookup. Accounts made before recovery codes existed have none until the player generates them.
+CREATE TABLE recovery_codes (
+    user_id INT UNSIGNED NOT NULL,
+    code_hash CHAR(64) NOT NULL,
+    PRIMARY KEY (user_id, code_hash),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
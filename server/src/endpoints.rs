@@ -2,7 +2,11 @@ use serde::{Deserialize, Serialize};
 use sqlx::types::chrono;
 use sqlx::MySqlPool;
 
-use crate::models::{Block, Message, Run, User};
+use crate::filter;
+use crate::models::{AuditEntry, Block, Message, Replay, Report, Role, Run, User};
+use crate::passwords;
+use crate::text;
+use crate::tickets::RunTicket;
 
 #[derive(Serialize)]
 struct Response<T: Serialize> {
@@ -33,17 +37,42 @@ impl<T: Serialize> From<Response<T>> for tide::Response {
     }
 }
 
+// Id of the user when the credentials are right, the account isn't banned and has at least the role
+async fn authorize(user: &mut User, role: Role, pool: &MySqlPool) -> tide::Result<Option<u32>> {
+    if !user.username_in_use(pool).await? {
+        return Ok(None);
+    }
+    user.load_hash(pool).await?;
+    if user.verify()
+        && !user.is_banned(pool).await?
+        && user.suspended_until(pool).await?.is_none()
+        && user.role(pool).await? >= role
+    {
+        Ok(Some(user.id(pool).await?))
+    } else {
+        Ok(None)
+    }
+}
+
 pub async fn sign_up(mut request: tide::Request<MySqlPool>) -> tide::Result {
     let mut user: User = request.body_json().await?;
     let pool = request.state();
-    if user.username_in_use(pool).await? {
+    if User::username_holder(user.username(), pool)
+        .await?
+        .is_some()
+    {
         Ok(Response::error("Username is already in use.").into())
-    } else if !user.is_valid() {
-        Ok(Response::error("Invalid request.").into())
+    } else if !User::is_valid_username(user.username()) {
+        Ok(Response::error("Invalid username.").into())
+    } else if !filter::is_clean_username(user.username()) {
+        Ok(Response::error("This username is not allowed.").into())
+    } else if let Err(problem) = user.check_password() {
+        Ok(Response::error(problem).into())
     } else {
         user.generate_hash();
         user.save(pool).await?;
-        Ok(Response::ok(()).into())
+        let recovery_codes = user.generate_recovery_codes(pool).await?;
+        Ok(Response::ok(recovery_codes).into())
     }
 }
 
@@ -53,7 +82,19 @@ pub async fn log_in(mut request: tide::Request<MySqlPool>) -> tide::Result {
     if user.username_in_use(pool).await? {
         user.load_hash(pool).await?;
         if user.verify() {
-            return Ok(Response::ok(()).into());
+            user.upgrade_hash(pool).await?;
+            if user.is_banned(pool).await? {
+                return Ok(Response::error("This account is banned.").into());
+            }
+            if let Some(until) = user.suspended_until(pool).await? {
+                return Ok(Response::error(format!(
+                    "This account is suspended until {} UTC.",
+                    until
+                ))
+                .into());
+            }
+            // Lets the page offer moderation tools to those who can use them
+            return Ok(Response::ok(user.role(pool).await?.name()).into());
         }
     }
     Ok(Response::error("Incorrect username or password.").into())
@@ -72,13 +113,23 @@ pub async fn change_username(mut request: tide::Request<MySqlPool>) -> tide::Res
         mut user,
     } = request.body_json().await?;
     let pool = request.state();
+    let new_username = text::normalize_username(&new_username);
 
     user.load_hash(pool).await?;
-    if user.verify() {
+    if !user.verify() {
+        Ok(Response::error("Incorrect password.").into())
+    } else if !User::is_valid_username(&new_username) {
+        Ok(Response::error("Invalid username.").into())
+    } else if User::username_holder(&new_username, pool)
+        .await?
+        .is_some_and(|holder| holder != user.username())
+    {
+        Ok(Response::error("Username is already in use.").into())
+    } else if !filter::is_clean_username(&new_username) {
+        Ok(Response::error("This username is not allowed.").into())
+    } else {
         user.change_username(&new_username, pool).await?;
         Ok(Response::ok(()).into())
-    } else {
-        Ok(Response::error("Incorrect password.").into())
     }
 }
 
@@ -97,9 +148,55 @@ pub async fn change_password(mut request: tide::Request<MySqlPool>) -> tide::Res
     let pool = request.state();
 
     user.load_hash(pool).await?;
-    if user.verify() {
+    if !user.verify() {
+        Ok(Response::error("Incorrect password.").into())
+    } else if let Err(problem) = passwords::check(&new_password, user.username()) {
+        Ok(Response::error(problem).into())
+    } else {
         user.change_password(&new_password, pool).await?;
         Ok(Response::ok(()).into())
+    }
+}
+
+pub async fn reset_password(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    #[derive(Deserialize)]
+    struct Data {
+        user: User,
+        #[serde(rename = "recoveryCode")]
+        recovery_code: String,
+        #[serde(rename = "newPassword")]
+        new_password: String,
+    }
+
+    let Data {
+        mut user,
+        recovery_code,
+        new_password,
+    } = request.body_json().await?;
+    let pool = request.state();
+
+    // The policy is checked first so a rejected password doesn't use up the code
+    if !user.username_in_use(pool).await? {
+        Ok(Response::error("Incorrect username or recovery code.").into())
+    } else if let Err(problem) = passwords::check(&new_password, user.username()) {
+        Ok(Response::error(problem).into())
+    } else if !user.redeem_recovery_code(&recovery_code, pool).await? {
+        Ok(Response::error("Incorrect username or recovery code.").into())
+    } else {
+        user.load_hash(pool).await?;
+        user.change_password(&new_password, pool).await?;
+        Ok(Response::ok(()).into())
+    }
+}
+
+pub async fn regenerate_recovery_codes(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    let mut user: User = request.body_json().await?;
+    let pool = request.state();
+
+    user.load_hash(pool).await?;
+    if user.verify() {
+        let recovery_codes = user.generate_recovery_codes(pool).await?;
+        Ok(Response::ok(recovery_codes).into())
     } else {
         Ok(Response::error("Incorrect password.").into())
     }
@@ -117,21 +214,106 @@ pub async fn delete_account(mut request: tide::Request<MySqlPool>) -> tide::Resu
     }
 }
 
+pub async fn start_run(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    #[derive(Deserialize)]
+    struct Data {
+        user: User,
+        difficulty: String,
+        // Id of the run being raced, the game is then played with the seed and difficulty of its replay
+        race: Option<u32>,
+    }
+
+    let Data {
+        mut user,
+        difficulty,
+        race,
+    } = request.body_json().await?;
+    let pool = request.state();
+    let Some(user_id) = authorize(&mut user, Role::Player, pool).await? else {
+        return Ok(Response::error("Invalid user.").into());
+    };
+    let (seed, difficulty) = match race {
+        Some(run_id) => {
+            let replay = match Run::fetch(run_id, pool).await? {
+                Some(run) if !run.is_hidden() => Replay::fetch(run_id, pool).await?,
+                _ => None,
+            };
+            let race = replay.and_then(|replay| {
+                let replay: serde_json::Value = serde_json::from_str(&replay.data).ok()?;
+                let seed = u32::try_from(replay["seed"].as_u64()?).ok()?;
+                Some((seed, replay["rules"]["difficulty"].as_str()?.to_string()))
+            });
+            match race {
+                Some((seed, difficulty)) => (Some(seed), difficulty),
+                None => return Ok(Response::error("This run can't be raced.").into()),
+            }
+        }
+        None => (None, difficulty),
+    };
+    if !Run::is_valid_difficulty(&difficulty) {
+        return Ok(Response::error("Invalid difficulty.").into());
+    }
+
+    #[derive(Serialize)]
+    struct OutputTicket {
+        ticket: String,
+        seed: u32,
+    }
+
+    let ticket = RunTicket::issue(user_id, seed, &difficulty);
+    Ok(Response::ok(OutputTicket {
+        ticket: ticket.encode(),
+        seed: ticket.seed,
+    })
+    .into())
+}
+
 pub async fn submit_run(mut request: tide::Request<MySqlPool>) -> tide::Result {
     #[derive(Deserialize)]
     struct Data {
         user: User,
         run: Run,
+        replay: Option<serde_json::Value>,
+        ticket: String,
     }
 
-    let Data { mut user, mut run } = request.body_json().await?;
+    let Data {
+        mut user,
+        mut run,
+        replay,
+        ticket,
+    } = request.body_json().await?;
     let pool = request.state();
-    user.load_hash(pool).await?;
-    if !user.verify() {
-        Ok(Response::error("Invalid user.").into())
+    let Some(replay) = replay else {
+        return Ok(Response::error("Runs need a replay.").into());
+    };
+    let Some(user_id) = authorize(&mut user, Role::Player, pool).await? else {
+        return Ok(Response::error("Invalid user.").into());
+    };
+    let ticket = match RunTicket::decode(&ticket) {
+        Some(ticket) if ticket.user_id == user_id => ticket,
+        _ => return Ok(Response::error("Invalid run ticket.").into()),
+    };
+    // The replay has to be of the game the ticket was issued for
+    let replay_matches = replay["seed"].as_u64() == Some(ticket.seed as u64)
+        && replay["score"].as_u64() == Some(run.score() as u64)
+        && replay["rules"]["difficulty"].as_str() == Some(ticket.difficulty.as_str())
+        && run.difficulty() == ticket.difficulty;
+    let data = replay.to_string();
+    if ticket.is_expired() {
+        Ok(Response::error("Run ticket has expired.").into())
+    } else if !ticket.is_plausible(run.score(), run.duration()) || !replay_matches {
+        Ok(Response::error("Run doesn't match its ticket.").into())
+    } else if !run.is_valid() {
+        Ok(Response::error("Invalid run.").into())
+    } else if data.len() > Replay::MAX_SIZE {
+        Ok(Response::error("Replay is too large.").into())
+    } else if !ticket.redeem(pool).await? {
+        Ok(Response::error("Run ticket was already used.").into())
     } else {
         run.generate_time();
-        run.submit_for_user(&user, pool).await?;
+        let run_id = run.submit_for_user(&user, pool).await?;
+        Replay { run_id, data }.save(pool).await?;
         Ok(Response::ok(()).into())
     }
 }
@@ -140,36 +322,84 @@ pub async fn get_runs(mut request: tide::Request<MySqlPool>) -> tide::Result {
     #[derive(Deserialize)]
     struct Data {
         user: Option<User>,
+        difficulty: Option<String>,
     }
 
-    let Data { user } = request.body_json().await?;
+    let Data { user, difficulty } = request.body_json().await?;
     let pool = request.state();
+    let difficulty = difficulty.unwrap_or_else(|| "normal".to_string());
+    if !Run::is_valid_difficulty(&difficulty) {
+        return Ok(Response::error("Invalid difficulty.").into());
+    }
 
     let runs = if let Some(user) = user {
-        user.fetch_runs(pool).await?
+        user.fetch_runs(&difficulty, pool).await?
     } else {
-        Run::fetch_all(pool).await?
+        Run::fetch_all(&difficulty, pool).await?
     };
 
     #[derive(Serialize)]
     struct OutputRun {
+        id: u32,
         username: String,
         score: u32,
+        difficulty: String,
         time: String,
+        duration: Option<f32>,
+        #[serde(rename = "obstaclesPassed")]
+        obstacles_passed: Option<u32>,
+        #[serde(rename = "maxSpeed")]
+        max_speed: Option<f32>,
+        rules: Option<serde_json::Value>,
+        version: Option<String>,
     }
 
     let mut output_runs = Vec::with_capacity(runs.len());
     for run in &runs {
         output_runs.push(OutputRun {
+            id: run.id().unwrap(),
             username: run.get_username(pool).await?,
             score: run.score(),
+            difficulty: run.difficulty().to_string(),
             time: run.time().unwrap().to_string(),
+            duration: run.duration(),
+            obstacles_passed: run.obstacles_passed(),
+            max_speed: run.max_speed(),
+            rules: run
+                .rules()
+                .and_then(|rules| serde_json::from_str(rules).ok()),
+            version: run.version().map(str::to_string),
         });
     }
 
     Ok(Response::ok(output_runs).into())
 }
 
+pub async fn get_replay(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    #[derive(Deserialize)]
+    struct Data {
+        run_id: u32,
+    }
+
+    let Data { run_id } = request.body_json().await?;
+    let pool = request.state();
+
+    // Hidden runs are left out of the listings, their replays are just as gone
+    if !Run::fetch(run_id, pool)
+        .await?
+        .is_some_and(|run| !run.is_hidden())
+    {
+        return Ok(Response::error("Run not found.").into());
+    }
+    match Replay::fetch(run_id, pool).await? {
+        Some(replay) => {
+            let replay: serde_json::Value = serde_json::from_str(&replay.data)?;
+            Ok(Response::ok(replay).into())
+        }
+        None => Ok(Response::error("This run has no replay.").into()),
+    }
+}
+
 pub async fn send_message(mut request: tide::Request<MySqlPool>) -> tide::Result {
     #[derive(Deserialize)]
     struct Data {
@@ -181,18 +411,22 @@ pub async fn send_message(mut request: tide::Request<MySqlPool>) -> tide::Result
     let Data { mut from, to, text } = request.body_json().await?;
     let pool = request.state();
 
-    from.load_hash(pool).await?;
-    if !from.verify() {
-        Ok(Response::error("Invalid sender.").into())
-    } else if text.len() > 500 {
-        Ok(Response::error("Message text too long.").into())
+    let Some(from_id) = authorize(&mut from, Role::Player, pool).await? else {
+        return Ok(Response::error("Invalid sender.").into());
+    };
+    if !Message::is_valid_text(&text) {
+        Ok(Response::error("Message text is empty, too long or has invalid characters.").into())
+    } else if !filter::is_clean_text(&text) {
+        Ok(Response::error("Message contains words that are not allowed.").into())
+    } else if Message::count_recent(from_id, pool).await? >= Message::rate_limit().limit {
+        Ok(Response::error("You are sending messages too quickly, wait a moment.").into())
     } else if !to.username_in_use(pool).await? {
         Ok(Response::error("Recipient does not exist.").into())
     } else if to.has_blocked(&from, pool).await? {
         Ok(Response::error("Recipient has blocked you.").into())
     } else {
         let message = Message::new(
-            from.id(pool).await?,
+            from_id,
             to.id(pool).await?,
             chrono::Utc::now().naive_utc(),
             text,
@@ -269,3 +503,385 @@ pub async fn block(mut request: tide::Request<MySqlPool>) -> tide::Result {
         Ok(Response::ok(()).into())
     }
 }
+
+pub async fn report(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    #[derive(Deserialize)]
+    #[serde(tag = "kind", rename_all = "lowercase")]
+    enum Target {
+        Message { id: u32 },
+        Run { id: u32 },
+        User(User),
+    }
+
+    #[derive(Deserialize)]
+    struct Data {
+        user: User,
+        target: Target,
+        reason: String,
+    }
+
+    let Data {
+        mut user,
+        target,
+        reason,
+    } = request.body_json().await?;
+    let pool = request.state();
+    let Some(reporter_id) = authorize(&mut user, Role::Player, pool).await? else {
+        return Ok(Response::error("Invalid user.").into());
+    };
+    if reason.trim().is_empty() {
+        return Ok(Response::error("Give a reason for the report.").into());
+    } else if !Report::is_valid_reason(&reason) {
+        return Ok(Response::error("Invalid reason.").into());
+    }
+
+    let (target_kind, target_id) = match target {
+        // Players can only report messages that were sent to them
+        Target::Message { id } => match Message::fetch(id, pool).await? {
+            Some(message) if message.to_user_id() == reporter_id => ("message", id),
+            _ => return Ok(Response::error("Message does not exist.").into()),
+        },
+        Target::Run { id } => match Run::fetch(id, pool).await? {
+            Some(run) if !run.is_hidden() => ("run", id),
+            _ => return Ok(Response::error("Run does not exist.").into()),
+        },
+        Target::User(target) => {
+            if !target.username_in_use(pool).await? {
+                return Ok(Response::error("User does not exist.").into());
+            }
+            let id = target.id(pool).await?;
+            if id == reporter_id {
+                return Ok(Response::error("You can't report yourself.").into());
+            }
+            ("user", id)
+        }
+    };
+
+    let report = Report::new(reporter_id, target_kind, target_id, reason);
+    if report.is_duplicate(pool).await? {
+        Ok(Response::error("You already reported this.").into())
+    } else {
+        report.save(pool).await?;
+        Ok(Response::ok(()).into())
+    }
+}
+
+// Id and username of whoever is responsible for the reported content and what it is, None once
+// it's gone
+async fn describe_report(
+    report: &Report,
+    pool: &MySqlPool,
+) -> tide::Result<Option<(u32, String, String)>> {
+    let id = report.target_id();
+    let description = match report.target_kind() {
+        "message" => match Message::fetch(id, pool).await? {
+            Some(message) => Some((
+                message.from_user_id(),
+                message.sender_username(pool).await?,
+                message.text().to_string(),
+            )),
+            None => None,
+        },
+        "run" => match Run::fetch(id, pool).await? {
+            Some(run) => Some((
+                run.user_id().unwrap(),
+                run.get_username(pool).await?,
+                format!("{} points on {}", run.score(), run.difficulty()),
+            )),
+            None => None,
+        },
+        _ => match User::username_from_id(id, pool).await {
+            Ok(username) => Some((id, username.clone(), username)),
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(error) => return Err(error.into()),
+        },
+    };
+    Ok(description)
+}
+
+pub async fn moderation_get_reports(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    let mut user: User = request.body_json().await?;
+    let pool = request.state();
+    if authorize(&mut user, Role::Moderator, pool).await?.is_none() {
+        return Ok(Response::error("Not allowed.").into());
+    }
+
+    #[derive(Serialize)]
+    struct OutputReport {
+        id: u32,
+        reporter: String,
+        #[serde(rename = "targetKind")]
+        target_kind: String,
+        #[serde(rename = "targetId")]
+        target_id: u32,
+        offender: Option<String>,
+        content: Option<String>,
+        reason: String,
+        time: String,
+    }
+
+    let reports = Report::fetch_all_open(pool).await?;
+    let mut output_reports = Vec::with_capacity(reports.len());
+    for report in &reports {
+        let description = describe_report(report, pool).await?;
+        output_reports.push(OutputReport {
+            id: report.id(),
+            reporter: report.reporter_username(pool).await?,
+            target_kind: report.target_kind().to_string(),
+            target_id: report.target_id(),
+            offender: description
+                .as_ref()
+                .map(|(_, offender, _)| offender.clone()),
+            content: description.map(|(_, _, content)| content),
+            reason: report.reason().to_string(),
+            time: report.time().to_string(),
+        });
+    }
+
+    Ok(Response::ok(output_reports).into())
+}
+
+pub async fn moderation_resolve_report(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    #[derive(Deserialize)]
+    #[serde(tag = "action", rename_all = "lowercase")]
+    enum Resolution {
+        Dismiss,
+        Delete,
+        Suspend { days: u32 },
+    }
+
+    #[derive(Deserialize)]
+    struct Data {
+        user: User,
+        report_id: u32,
+        resolution: Resolution,
+    }
+
+    let Data {
+        mut user,
+        report_id,
+        resolution,
+    } = request.body_json().await?;
+    let pool = request.state();
+    let Some(actor_id) = authorize(&mut user, Role::Moderator, pool).await? else {
+        return Ok(Response::error("Not allowed.").into());
+    };
+    let Some(report) = Report::fetch_open(report_id, pool).await? else {
+        return Ok(Response::error("Report does not exist or was already resolved.").into());
+    };
+    let target_id = report.target_id();
+
+    match resolution {
+        Resolution::Dismiss => {
+            report.resolve("dismissed", actor_id, pool).await?;
+            AuditEntry::new(actor_id, "dismiss", "report", report_id)
+                .save(pool)
+                .await?;
+        }
+        Resolution::Delete => {
+            match report.target_kind() {
+                "message" => {
+                    Message::delete(target_id, pool).await?;
+                    AuditEntry::new(actor_id, "delete", "message", target_id)
+                        .save(pool)
+                        .await?;
+                }
+                // Deleting runs is left to admins, taking them off the leaderboards is enough here
+                "run" => {
+                    Run::set_hidden(target_id, true, pool).await?;
+                    AuditEntry::new(actor_id, "hide", "run", target_id)
+                        .save(pool)
+                        .await?;
+                }
+                _ => {
+                    return Ok(Response::error("Accounts can only be suspended.").into());
+                }
+            }
+            report.resolve("deleted", actor_id, pool).await?;
+        }
+        Resolution::Suspend { days } => {
+            if !(1..=365).contains(&days) {
+                return Ok(Response::error("Suspensions last from 1 to 365 days.").into());
+            }
+            let Some((offender_id, _, _)) = describe_report(&report, pool).await? else {
+                return Ok(Response::error("Reported content no longer exists.").into());
+            };
+            if User::role_from_id(offender_id, pool).await? >= user.role(pool).await? {
+                return Ok(Response::error("Not allowed.").into());
+            }
+            let until = chrono::DateTime::from_timestamp_millis(
+                chrono::Utc::now().timestamp_millis() + days as i64 * 24 * 60 * 60 * 1000,
+            )
+            .unwrap()
+            .naive_utc();
+            User::suspend(offender_id, until, pool).await?;
+            report.resolve("suspended", actor_id, pool).await?;
+            AuditEntry::new(actor_id, "suspend", "user", offender_id)
+                .save(pool)
+                .await?;
+        }
+    }
+    Ok(Response::ok(()).into())
+}
+
+pub async fn moderation_get_runs(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    #[derive(Deserialize)]
+    struct Data {
+        user: User,
+        difficulty: String,
+    }
+
+    let Data {
+        mut user,
+        difficulty,
+    } = request.body_json().await?;
+    let pool = request.state();
+    if authorize(&mut user, Role::Moderator, pool).await?.is_none() {
+        return Ok(Response::error("Not allowed.").into());
+    }
+    if !Run::is_valid_difficulty(&difficulty) {
+        return Ok(Response::error("Invalid difficulty.").into());
+    }
+
+    #[derive(Serialize)]
+    struct OutputRun {
+        id: u32,
+        username: String,
+        score: u32,
+        time: String,
+        duration: Option<f32>,
+        version: Option<String>,
+        hidden: bool,
+    }
+
+    let runs = Run::fetch_for_moderation(&difficulty, pool).await?;
+    let mut output_runs = Vec::with_capacity(runs.len());
+    for run in &runs {
+        output_runs.push(OutputRun {
+            id: run.id().unwrap(),
+            username: run.get_username(pool).await?,
+            score: run.score(),
+            time: run.time().unwrap().to_string(),
+            duration: run.duration(),
+            version: run.version().map(str::to_string),
+            hidden: run.is_hidden(),
+        });
+    }
+
+    Ok(Response::ok(output_runs).into())
+}
+
+pub async fn moderation_hide_run(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    #[derive(Deserialize)]
+    struct Data {
+        user: User,
+        run_id: u32,
+        hidden: bool,
+    }
+
+    let Data {
+        mut user,
+        run_id,
+        hidden,
+    } = request.body_json().await?;
+    let pool = request.state();
+    let Some(actor_id) = authorize(&mut user, Role::Moderator, pool).await? else {
+        return Ok(Response::error("Not allowed.").into());
+    };
+    if !Run::set_hidden(run_id, hidden, pool).await? {
+        return Ok(Response::error("Run does not exist.").into());
+    }
+    let action = if hidden { "hide" } else { "unhide" };
+    AuditEntry::new(actor_id, action, "run", run_id)
+        .save(pool)
+        .await?;
+    Ok(Response::ok(()).into())
+}
+
+pub async fn moderation_delete_run(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    #[derive(Deserialize)]
+    struct Data {
+        user: User,
+        run_id: u32,
+    }
+
+    let Data { mut user, run_id } = request.body_json().await?;
+    let pool = request.state();
+    let Some(actor_id) = authorize(&mut user, Role::Admin, pool).await? else {
+        return Ok(Response::error("Not allowed.").into());
+    };
+    if !Run::delete(run_id, pool).await? {
+        return Ok(Response::error("Run does not exist.").into());
+    }
+    AuditEntry::new(actor_id, "delete", "run", run_id)
+        .save(pool)
+        .await?;
+    Ok(Response::ok(()).into())
+}
+
+pub async fn moderation_ban_user(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    #[derive(Deserialize)]
+    struct Data {
+        user: User,
+        target: User,
+        banned: bool,
+    }
+
+    let Data {
+        mut user,
+        target,
+        banned,
+    } = request.body_json().await?;
+    let pool = request.state();
+    let Some(actor_id) = authorize(&mut user, Role::Moderator, pool).await? else {
+        return Ok(Response::error("Not allowed.").into());
+    };
+    if !target.username_in_use(pool).await? {
+        return Ok(Response::error("User does not exist.").into());
+    }
+    // Moderators can't ban each other, only admins can ban moderators
+    if target.role(pool).await? >= user.role(pool).await? {
+        return Ok(Response::error("Not allowed.").into());
+    }
+    target.set_banned(banned, pool).await?;
+    let action = if banned { "ban" } else { "unban" };
+    AuditEntry::new(actor_id, action, "user", target.id(pool).await?)
+        .save(pool)
+        .await?;
+    Ok(Response::ok(()).into())
+}
+
+pub async fn moderation_get_audit_log(mut request: tide::Request<MySqlPool>) -> tide::Result {
+    let mut user: User = request.body_json().await?;
+    let pool = request.state();
+    if authorize(&mut user, Role::Admin, pool).await?.is_none() {
+        return Ok(Response::error("Not allowed.").into());
+    }
+
+    #[derive(Serialize)]
+    struct OutputEntry {
+        id: u32,
+        actor: Option<String>,
+        action: String,
+        #[serde(rename = "targetKind")]
+        target_kind: String,
+        #[serde(rename = "targetId")]
+        target_id: u32,
+        time: String,
+    }
+
+    let entries = AuditEntry::fetch_all(pool).await?;
+    let mut output_entries = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        output_entries.push(OutputEntry {
+            id: entry.id(),
+            actor: entry.actor_username(pool).await?,
+            action: entry.action().to_string(),
+            target_kind: entry.target_kind().to_string(),
+            target_id: entry.target_id(),
+            time: entry.time().to_string(),
+        });
+    }
+
+    Ok(Response::ok(output_entries).into())
+}
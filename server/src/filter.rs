@@ -0,0 +1,193 @@
+use std::fs;
+use std::sync::OnceLock;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// Runs of the same letter, "fuuuck" is [('f', 1), ('u', 3), ('c', 1), ('k', 1)]
+type Runs = Vec<(char, usize)>;
+
+static WORDS: OnceLock<Vec<Runs>> = OnceLock::new();
+// Innocent words that contain filtered ones, like "Scunthorpe"
+static ALLOWED: OnceLock<Vec<Runs>> = OnceLock::new();
+
+// One word per line, empty lines and lines starting with # are skipped
+fn load(path: &str) -> Vec<Runs> {
+    match fs::read_to_string(path) {
+        Ok(list) => list
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|word| runs(&normalize(word)))
+            .filter(|word| !word.is_empty())
+            .collect(),
+        Err(error) => {
+            tide::log::warn!("Couldn't read the content filter list {}: {}", path, error);
+            Vec::new()
+        }
+    }
+}
+
+pub fn init(words_path: &str, allowed_path: &str) {
+    WORDS
+        .set(load(words_path))
+        .expect("Content filter already initialized!");
+    ALLOWED
+        .set(load(allowed_path))
+        .expect("Content filter already initialized!");
+}
+
+// Letters that look like or are commonly typed in place of latin ones
+fn unconfuse(c: char) -> char {
+    match c {
+        '0' | 'о' | 'ο' | 'σ' => 'o',
+        '1' | '!' | '|' | 'і' | 'ι' | 'ı' => 'i',
+        '3' | 'е' | 'ε' => 'e',
+        '4' | '@' | 'а' | 'α' => 'a',
+        '5' | '$' | 'ѕ' => 's',
+        '7' | '+' | 'т' | 'τ' => 't',
+        '8' | 'в' | 'β' => 'b',
+        '9' => 'g',
+        'с' | 'ς' => 'c',
+        'р' | 'ρ' => 'p',
+        'х' | 'χ' => 'x',
+        'у' | 'γ' => 'y',
+        'к' | 'κ' => 'k',
+        'м' => 'm',
+        'н' | 'η' => 'h',
+        'ν' => 'v',
+        'ј' => 'j',
+        'ԁ' => 'd',
+        'υ' => 'u',
+        _ => c,
+    }
+}
+
+// Lowercase latin letters with accents and lookalikes replaced, everything else becomes a space
+fn normalize(text: &str) -> String {
+    text.nfkd()
+        .filter(|&c| !is_combining_mark(c))
+        .flat_map(char::to_lowercase)
+        .map(unconfuse)
+        .map(|c| if c.is_ascii_lowercase() { c } else { ' ' })
+        .collect()
+}
+
+fn runs(word: &str) -> Runs {
+    let mut runs: Runs = Vec::new();
+    for c in word.chars().filter(|&c| c != ' ') {
+        match runs.last_mut() {
+            Some((last, count)) if *last == c => *count += 1,
+            _ => runs.push((c, 1)),
+        }
+    }
+    runs
+}
+
+// Letters may be repeated more often than in the word but not less, so "ass" doesn't match "as"
+fn matches_at(word: &[(char, usize)], text: &[(char, usize)]) -> bool {
+    word.len() <= text.len()
+        && word
+            .iter()
+            .zip(text)
+            .all(|((c, count), (text_c, text_count))| c == text_c && count <= text_count)
+}
+
+// Words of the text, with letters typed apart like "f u c k" joined back together
+fn words(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut spelled_out = false;
+    for word in text.split(' ').filter(|word| !word.is_empty()) {
+        let single_letter = word.len() == 1;
+        match words.last_mut() {
+            Some(last) if single_letter && spelled_out => last.push_str(word),
+            _ => words.push(word.to_string()),
+        }
+        spelled_out = single_letter;
+    }
+    words
+}
+
+// Messages only match whole words so the filter doesn't catch innocent words containing bad ones
+pub fn is_clean_text(text: &str) -> bool {
+    let words = WORDS.get().expect("Content filter not initialized!");
+    self::words(&normalize(text)).iter().all(|text_word| {
+        let text_word = runs(text_word);
+        !words
+            .iter()
+            .any(|word| word.len() == text_word.len() && matches_at(word, &text_word))
+    })
+}
+
+// Usernames are often several words run together, so any part of them can match unless it's part
+// of an allowed word
+pub fn is_clean_username(username: &str) -> bool {
+    let words = WORDS.get().expect("Content filter not initialized!");
+    let allowed = ALLOWED.get().expect("Content filter not initialized!");
+    let username = &runs(&normalize(username))[..];
+    let allowed_spans = (0..username.len())
+        .flat_map(|start| {
+            allowed
+                .iter()
+                .filter(move |word| matches_at(word, &username[start..]))
+                .map(move |word| start..start + word.len())
+        })
+        .collect::<Vec<_>>();
+    !(0..username.len()).any(|start| {
+        words.iter().any(|word| {
+            let end = start + word.len();
+            matches_at(word, &username[start..])
+                && !allowed_spans
+                    .iter()
+                    .any(|span| span.start <= start && end <= span.end)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init() {
+        WORDS.get_or_init(|| load("filter-words.txt"));
+        ALLOWED.get_or_init(|| load("filter-allowed.txt"));
+    }
+
+    #[test]
+    fn allows_names_containing_filtered_words() {
+        init();
+        for username in [
+            "Hancock",
+            "Peacock",
+            "Scunthorpe",
+            "peacock_fan99",
+            "HitchcockFan",
+        ] {
+            assert!(is_clean_username(username), "{}", username);
+        }
+    }
+
+    #[test]
+    fn rejects_filtered_words_in_names() {
+        init();
+        for username in [
+            "xXfuckXx",
+            "FuckPeacocks",
+            "Peacockfuck",
+            "c0ck",
+            "sh1thead",
+            "ＣＯＣＫ",
+        ] {
+            assert!(!is_clean_username(username), "{}", username);
+        }
+    }
+
+    #[test]
+    fn matches_whole_words_in_text() {
+        init();
+        assert!(is_clean_text("Nice run, see you in Scunthorpe"));
+        assert!(is_clean_text("cocktails after the race?"));
+        assert!(!is_clean_text("what the fuuuck"));
+        assert!(!is_clean_text("f u c k this"));
+    }
+}
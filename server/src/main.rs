@@ -1,14 +1,56 @@
 use std::env;
+use std::future::Future;
+use std::pin::Pin;
 
-use sqlx::mysql::MySqlPoolOptions;
+use sqlx::mysql::{MySqlPool, MySqlPoolOptions};
 
 mod endpoints;
+mod filter;
 mod models;
+mod passwords;
+mod text;
+mod tickets;
+
+// Cross-origin isolation lets the benchmark share memory between its rasterization threads, only
+// the benchmark page and its workers get it so nothing else has to opt into being embedded
+fn cross_origin_isolation(
+    request: tide::Request<MySqlPool>,
+    next: tide::Next<'_, MySqlPool>,
+) -> Pin<Box<dyn Future<Output = tide::Result> + Send + '_>> {
+    Box::pin(async move {
+        let path = request.url().path().to_string();
+        let mut response = next.run(request).await;
+        if path == "/benchmark.html"
+            || path == "/benchmark-worker.js"
+            || path.starts_with("/snippets/")
+        {
+            response.insert_header("Cross-Origin-Opener-Policy", "same-origin");
+            response.insert_header("Cross-Origin-Embedder-Policy", "require-corp");
+        }
+        Ok(response)
+    })
+}
 
 // docker run --name mariadbtest -e MYSQL_ROOT_PASSWORD=mypass -p 3306:3306 -d docker.io/library/mariadb:10.7
 #[async_std::main]
 async fn main() -> tide::Result<()> {
     tide::log::with_level(tide::log::LevelFilter::Warn);
+    passwords::init_policy(passwords::PasswordPolicy::load(
+        env::var("PASSWORD_MIN_LENGTH").map_or(Ok(8), |length| length.parse())?,
+        env::var("PASSWORD_MAX_LENGTH").map_or(Ok(128), |length| length.parse())?,
+        &env::var("COMMON_PASSWORDS").unwrap_or_else(|_| "common-passwords.txt".to_string()),
+    ));
+    tickets::init_secret(env::var("RUN_TICKET_SECRET").ok());
+    passwords::init_recovery_code_secret(env::var("RECOVERY_CODE_SECRET").ok());
+    filter::init(
+        &env::var("CONTENT_FILTER_WORDS").unwrap_or_else(|_| "filter-words.txt".to_string()),
+        &env::var("CONTENT_FILTER_ALLOWED").unwrap_or_else(|_| "filter-allowed.txt".to_string()),
+    );
+    models::Message::init_rate_limit(models::RateLimit {
+        limit: env::var("MESSAGE_RATE_LIMIT").map_or(Ok(5), |limit| limit.parse())?,
+        window_seconds: env::var("MESSAGE_RATE_WINDOW_SECONDS")
+            .map_or(Ok(60), |seconds| seconds.parse())?,
+    });
 
     let pool = MySqlPoolOptions::new()
         .connect(&env::var("DATABASE_URL")?)
@@ -16,17 +58,39 @@ async fn main() -> tide::Result<()> {
 
     let mut app = tide::with_state(pool.clone());
 
+    app.with(cross_origin_isolation);
+
     app.at("/").serve_file("../www/index.html")?;
     app.at("/sign-up").post(endpoints::sign_up);
     app.at("/log-in").post(endpoints::log_in);
     app.at("/change-username").put(endpoints::change_username);
     app.at("/change-password").put(endpoints::change_password);
+    app.at("/reset-password").put(endpoints::reset_password);
+    app.at("/regenerate-recovery-codes")
+        .post(endpoints::regenerate_recovery_codes);
     app.at("/delete-account").delete(endpoints::delete_account);
+    app.at("/start-run").post(endpoints::start_run);
     app.at("/submit-run").post(endpoints::submit_run);
     app.at("/get-runs").post(endpoints::get_runs);
+    app.at("/get-replay").post(endpoints::get_replay);
     app.at("/message").post(endpoints::send_message);
     app.at("/get-messages").post(endpoints::get_messages);
     app.at("/block").post(endpoints::block);
+    app.at("/report").post(endpoints::report);
+    app.at("/moderation/get-reports")
+        .post(endpoints::moderation_get_reports);
+    app.at("/moderation/resolve-report")
+        .put(endpoints::moderation_resolve_report);
+    app.at("/moderation/get-runs")
+        .post(endpoints::moderation_get_runs);
+    app.at("/moderation/hide-run")
+        .put(endpoints::moderation_hide_run);
+    app.at("/moderation/delete-run")
+        .delete(endpoints::moderation_delete_run);
+    app.at("/moderation/ban-user")
+        .put(endpoints::moderation_ban_user);
+    app.at("/moderation/get-audit-log")
+        .post(endpoints::moderation_get_audit_log);
 
     app.at("/").serve_dir("../www")?;
 
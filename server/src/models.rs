@@ -1,50 +1,100 @@
+use std::sync::OnceLock;
+
 use serde::{Deserialize, Serialize};
 use sqlx::types::chrono;
 use sqlx::MySqlPool;
 
+use crate::{passwords, text};
+
+// Ordered by privilege, every role can do everything the ones before it can
+#[derive(Copy, Clone, PartialEq, PartialOrd)]
+pub enum Role {
+    Player,
+    Moderator,
+    Admin,
+}
+
+impl Role {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "player" => Some(Role::Player),
+            "moderator" => Some(Role::Moderator),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Player => "player",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct User {
+    #[serde(deserialize_with = "text::deserialize_username")]
     username: String,
     password: Option<String>,
     password_hash: Option<String>,
 }
 
 impl User {
+    pub const MAX_USERNAME_LENGTH: usize = 20;
+
     pub fn is_valid_username(username: &str) -> bool {
-        !username.is_empty() && username.len() <= 20
+        (1..=Self::MAX_USERNAME_LENGTH).contains(&text::length(username))
+            && text::is_well_formed_username(username)
     }
 
-    pub fn is_valid_password(password: &str) -> bool {
-        password.len() >= 8 && password.len() <= 50
+    pub fn username(&self) -> &str {
+        &self.username
     }
 
-    pub fn is_valid(&self) -> bool {
-        Self::is_valid_username(&self.username)
-            && Self::is_valid_password(self.password.as_ref().expect("Password missing!"))
+    pub fn check_password(&self) -> Result<(), String> {
+        passwords::check(
+            self.password.as_ref().expect("Password missing!"),
+            &self.username,
+        )
     }
 
     pub async fn username_in_use(&self, pool: &MySqlPool) -> Result<bool, sqlx::Error> {
-        let result = sqlx::query!(r#"SELECT "x" FROM users WHERE username = ?"#, self.username)
-            .fetch_optional(pool)
-            .await?;
+        let result = sqlx::query!(
+            r#"SELECT "x" FROM users WHERE username_key = ?"#,
+            text::username_key(&self.username)
+        )
+        .fetch_optional(pool)
+        .await?;
         Ok(result.is_some())
     }
 
+    // Who has a username that only differs from this one in case or compatibility forms
+    pub async fn username_holder(
+        username: &str,
+        pool: &MySqlPool,
+    ) -> Result<Option<String>, sqlx::Error> {
+        Ok(sqlx::query!(
+            "SELECT username FROM users WHERE username_key = ?",
+            text::username_key(username)
+        )
+        .fetch_optional(pool)
+        .await?
+        .map(|data| data.username))
+    }
+
     pub fn generate_hash(&mut self) {
-        self.password_hash = Some(
-            bcrypt::hash(
-                self.password.as_ref().expect("Password missing!"),
-                bcrypt::DEFAULT_COST,
-            )
-            .unwrap(),
-        );
+        self.password_hash = Some(passwords::hash(
+            self.password.as_ref().expect("Password missing!"),
+        ));
     }
 
     pub async fn load_hash(&mut self, pool: &MySqlPool) -> Result<(), sqlx::Error> {
         self.password_hash = Some(
             sqlx::query!(
-                "SELECT password_hash FROM users WHERE username = ?",
-                self.username
+                "SELECT password_hash FROM users WHERE username_key = ?",
+                text::username_key(&self.username)
             )
             .fetch_one(pool)
             .await?
@@ -54,17 +104,27 @@ impl User {
     }
 
     pub fn verify(&self) -> bool {
-        bcrypt::verify(
+        passwords::verify(
             self.password.as_ref().expect("Password missing!"),
-            &self.password_hash.as_ref().expect("Password hash missing!"),
+            self.password_hash.as_ref().expect("Password hash missing!"),
         )
-        .unwrap()
+    }
+
+    // Stores the password hashed the current way if it was hashed some older way, only possible
+    // right after verifying since that's the only time the password is known to be right
+    pub async fn upgrade_hash(&mut self, pool: &MySqlPool) -> Result<(), sqlx::Error> {
+        if passwords::needs_rehash(self.password_hash.as_ref().expect("Password hash missing!")) {
+            let password = self.password.clone().expect("Password missing!");
+            self.change_password(&password, pool).await?;
+        }
+        Ok(())
     }
 
     pub async fn save(&self, pool: &MySqlPool) -> Result<(), sqlx::Error> {
         sqlx::query!(
-            "INSERT INTO users(username, password_hash) VALUES(?, ?)",
+            "INSERT INTO users(username, username_key, password_hash) VALUES(?, ?, ?)",
             self.username,
+            text::username_key(&self.username),
             self.password_hash.as_ref().expect("Password hash missing!")
         )
         .execute(pool)
@@ -78,9 +138,10 @@ impl User {
         pool: &MySqlPool,
     ) -> Result<(), sqlx::Error> {
         sqlx::query!(
-            "UPDATE users SET username = ? WHERE username = ? AND password_hash = ?",
+            "UPDATE users SET username = ?, username_key = ? WHERE username_key = ? AND password_hash = ?",
             new_username,
-            self.username,
+            text::username_key(new_username),
+            text::username_key(&self.username),
             self.password_hash.as_ref().expect("Password hash missing!"),
         )
         .execute(pool)
@@ -88,16 +149,56 @@ impl User {
         Ok(())
     }
 
+    // Replaces any earlier codes, the new ones are returned so they can be shown to the player once
+    pub async fn generate_recovery_codes(
+        &self,
+        pool: &MySqlPool,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let codes = passwords::generate_recovery_codes();
+        let user_id = self.id(pool).await?;
+        let mut transaction = pool.begin().await?;
+        sqlx::query!("DELETE FROM recovery_codes WHERE user_id = ?", user_id)
+            .execute(&mut *transaction)
+            .await?;
+        for code in &codes {
+            sqlx::query!(
+                "INSERT INTO recovery_codes(user_id, code_hash) VALUES(?, ?)",
+                user_id,
+                passwords::hash_recovery_code(code)
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(codes)
+    }
+
+    // Uses up the code, returns false when it isn't one of the user's unused codes
+    pub async fn redeem_recovery_code(
+        &self,
+        code: &str,
+        pool: &MySqlPool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM recovery_codes WHERE user_id = (SELECT id FROM users WHERE username_key = ?) AND code_hash = ?",
+            text::username_key(&self.username),
+            passwords::hash_recovery_code(code)
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn change_password(
         &mut self,
         new_password: &str,
         pool: &MySqlPool,
     ) -> Result<(), sqlx::Error> {
-        let new_hash = bcrypt::hash(new_password, bcrypt::DEFAULT_COST).unwrap();
+        let new_hash = passwords::hash(new_password);
         sqlx::query!(
-            "UPDATE users SET password_hash = ? WHERE username = ? AND password_hash = ?",
+            "UPDATE users SET password_hash = ? WHERE username_key = ? AND password_hash = ?",
             new_hash,
-            self.username,
+            text::username_key(&self.username),
             self.password_hash.as_ref().expect("Password hash missing!"),
         )
         .execute(pool)
@@ -106,11 +207,16 @@ impl User {
         Ok(())
     }
 
-    pub async fn fetch_runs(&self, pool: &MySqlPool) -> Result<Vec<Run>, sqlx::Error> {
+    pub async fn fetch_runs(
+        &self,
+        difficulty: &str,
+        pool: &MySqlPool,
+    ) -> Result<Vec<Run>, sqlx::Error> {
         let runs = sqlx::query_as!(
             Run,
-            r#"SELECT id as "id?", user_id as "user_id?", score, time as "time?" FROM runs WHERE user_id = (SELECT id FROM users WHERE username = ?)"#,
-            self.username
+            r#"SELECT id as "id?", user_id as "user_id?", score, difficulty, time as "time?", duration, obstacles_passed, max_speed, rules, version, hidden FROM runs WHERE user_id = (SELECT id FROM users WHERE username_key = ?) AND difficulty = ? AND hidden = 0"#,
+            text::username_key(&self.username),
+            difficulty
         )
             .fetch_all(pool)
             .await?;
@@ -132,8 +238,8 @@ impl User {
 
     pub async fn has_blocked(&self, other: &User, pool: &MySqlPool) -> Result<bool, sqlx::Error> {
         let blocked_ids: Vec<u32> = sqlx::query!(
-            "SELECT blocked_user_id FROM blocks WHERE blocking_user_id = (SELECT id FROM users WHERE username = ?)",
-            self.username,
+            "SELECT blocked_user_id FROM blocks WHERE blocking_user_id = (SELECT id FROM users WHERE username_key = ?)",
+            text::username_key(&self.username),
         )
             .fetch_all(pool)
             .await?
@@ -145,12 +251,84 @@ impl User {
     }
 
     pub async fn id(&self, pool: &MySqlPool) -> Result<u32, sqlx::Error> {
-        Ok(
-            sqlx::query!("SELECT id FROM users WHERE username = ?", self.username)
-                .fetch_one(pool)
-                .await?
-                .id,
+        Ok(sqlx::query!(
+            "SELECT id FROM users WHERE username_key = ?",
+            text::username_key(&self.username)
+        )
+        .fetch_one(pool)
+        .await?
+        .id)
+    }
+
+    pub async fn role(&self, pool: &MySqlPool) -> Result<Role, sqlx::Error> {
+        let role = sqlx::query!(
+            "SELECT role FROM users WHERE username_key = ?",
+            text::username_key(&self.username)
+        )
+        .fetch_one(pool)
+        .await?
+        .role;
+        Ok(Role::from_name(&role).unwrap_or(Role::Player))
+    }
+
+    pub async fn is_banned(&self, pool: &MySqlPool) -> Result<bool, sqlx::Error> {
+        let banned = sqlx::query!(
+            "SELECT banned FROM users WHERE username_key = ?",
+            text::username_key(&self.username)
         )
+        .fetch_one(pool)
+        .await?
+        .banned;
+        Ok(banned == 1)
+    }
+
+    pub async fn set_banned(&self, banned: bool, pool: &MySqlPool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE users SET banned = ? WHERE username_key = ?",
+            banned,
+            text::username_key(&self.username)
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    // Only returns the end of a suspension that is still running
+    pub async fn suspended_until(
+        &self,
+        pool: &MySqlPool,
+    ) -> Result<Option<chrono::NaiveDateTime>, sqlx::Error> {
+        let suspended_until = sqlx::query!(
+            "SELECT suspended_until FROM users WHERE username_key = ?",
+            text::username_key(&self.username)
+        )
+        .fetch_one(pool)
+        .await?
+        .suspended_until;
+        Ok(suspended_until.filter(|until| *until > chrono::Utc::now().naive_utc()))
+    }
+
+    pub async fn suspend(
+        id: u32,
+        until: chrono::NaiveDateTime,
+        pool: &MySqlPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE users SET suspended_until = ? WHERE id = ?",
+            until,
+            id
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn role_from_id(id: u32, pool: &MySqlPool) -> Result<Role, sqlx::Error> {
+        let role = sqlx::query!("SELECT role FROM users WHERE id = ?", id)
+            .fetch_one(pool)
+            .await?
+            .role;
+        Ok(Role::from_name(&role).unwrap_or(Role::Player))
     }
 
     pub async fn username_from_id(id: u32, pool: &MySqlPool) -> Result<String, sqlx::Error> {
@@ -162,8 +340,8 @@ impl User {
 
     pub async fn delete(&self, pool: &MySqlPool) -> Result<(), sqlx::Error> {
         sqlx::query!(
-            "DELETE FROM users WHERE username = ? AND password_hash = ?",
-            self.username,
+            "DELETE FROM users WHERE username_key = ? AND password_hash = ?",
+            text::username_key(&self.username),
             self.password_hash.as_ref().expect("Password hash missing!"),
         )
         .execute(pool)
@@ -177,36 +355,129 @@ pub struct Run {
     id: Option<u32>,
     user_id: Option<u32>,
     score: u32,
+    #[serde(default = "Run::default_difficulty")]
+    difficulty: String,
     time: Option<chrono::NaiveDateTime>,
+    // Reported by the game, missing for runs submitted before it did
+    #[serde(default)]
+    duration: Option<f32>,
+    #[serde(default, rename = "obstaclesPassed")]
+    obstacles_passed: Option<u32>,
+    #[serde(default, rename = "maxSpeed")]
+    max_speed: Option<f32>,
+    #[serde(default)]
+    rules: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+    // Set by moderators, never by the player submitting the run
+    #[serde(skip)]
+    hidden: i8,
 }
 
 impl Run {
+    // Names of the game's rule presets, leaderboards are kept separately for each
+    pub const DIFFICULTIES: [&'static str; 3] = ["easy", "normal", "hard"];
+
+    fn default_difficulty() -> String {
+        "normal".to_string()
+    }
+
+    pub fn is_valid_difficulty(difficulty: &str) -> bool {
+        Self::DIFFICULTIES.contains(&difficulty)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        Self::is_valid_difficulty(&self.difficulty)
+            && self
+                .duration
+                .is_none_or(|duration| duration.is_finite() && duration >= 0.0)
+            && self
+                .max_speed
+                .is_none_or(|speed| speed.is_finite() && speed >= 0.0)
+            && self.rules.as_ref().is_none_or(|rules| {
+                rules.len() <= 1000 && serde_json::from_str::<serde_json::Value>(rules).is_ok()
+            })
+            && self
+                .version
+                .as_ref()
+                .is_none_or(|version| version.len() <= 40)
+    }
+
     pub fn generate_time(&mut self) {
         self.time = Some(chrono::Utc::now().naive_utc());
     }
 
-    pub async fn submit_for_user(&self, user: &User, pool: &MySqlPool) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            "INSERT INTO runs(user_id, score, time) VALUES((SELECT id FROM users WHERE username = ?), ?, ?)",
-            user.username,
+    // Returns the id of the new run
+    pub async fn submit_for_user(&self, user: &User, pool: &MySqlPool) -> Result<u32, sqlx::Error> {
+        let result = sqlx::query!(
+            "INSERT INTO runs(user_id, score, difficulty, time, duration, obstacles_passed, max_speed, rules, version) VALUES((SELECT id FROM users WHERE username_key = ?), ?, ?, ?, ?, ?, ?, ?, ?)",
+            text::username_key(&user.username),
             self.score,
-            self.time.expect("Time missing!")
+            self.difficulty,
+            self.time.expect("Time missing!"),
+            self.duration,
+            self.obstacles_passed,
+            self.max_speed,
+            self.rules,
+            self.version
         )
             .execute(pool)
             .await?;
-        Ok(())
+        Ok(result.last_insert_id() as u32)
     }
 
-    pub async fn fetch_all(pool: &MySqlPool) -> Result<Vec<Run>, sqlx::Error> {
+    pub async fn fetch_all(difficulty: &str, pool: &MySqlPool) -> Result<Vec<Run>, sqlx::Error> {
         let runs = sqlx::query_as!(
             Self,
-            r#"SELECT id as "id?", user_id as "user_id?", score, time as "time?" FROM runs"#
+            r#"SELECT id as "id?", user_id as "user_id?", score, difficulty, time as "time?", duration, obstacles_passed, max_speed, rules, version, hidden FROM runs WHERE difficulty = ? AND hidden = 0"#,
+            difficulty
         )
         .fetch_all(pool)
         .await?;
         Ok(runs)
     }
 
+    pub async fn fetch(id: u32, pool: &MySqlPool) -> Result<Option<Run>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id as "id?", user_id as "user_id?", score, difficulty, time as "time?", duration, obstacles_passed, max_speed, rules, version, hidden FROM runs WHERE id = ?"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    // Includes hidden runs
+    pub async fn fetch_for_moderation(
+        difficulty: &str,
+        pool: &MySqlPool,
+    ) -> Result<Vec<Run>, sqlx::Error> {
+        let runs = sqlx::query_as!(
+            Self,
+            r#"SELECT id as "id?", user_id as "user_id?", score, difficulty, time as "time?", duration, obstacles_passed, max_speed, rules, version, hidden FROM runs WHERE difficulty = ?"#,
+            difficulty
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(runs)
+    }
+
+    // Returns false when there is no run with the id
+    pub async fn set_hidden(id: u32, hidden: bool, pool: &MySqlPool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!("UPDATE runs SET hidden = ? WHERE id = ?", hidden, id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Returns false when there is no run with the id, its replay goes with it
+    pub async fn delete(id: u32, pool: &MySqlPool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM runs WHERE id = ?", id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn get_username(&self, pool: &MySqlPool) -> Result<String, sqlx::Error> {
         Ok(
             sqlx::query!("SELECT username FROM users WHERE id = ?", self.user_id)
@@ -216,13 +487,49 @@ impl Run {
         )
     }
 
+    pub fn id(&self) -> Option<u32> {
+        self.id
+    }
+
+    pub fn user_id(&self) -> Option<u32> {
+        self.user_id
+    }
+
     pub fn score(&self) -> u32 {
         self.score
     }
 
+    pub fn difficulty(&self) -> &str {
+        &self.difficulty
+    }
+
     pub fn time(&self) -> Option<chrono::NaiveDateTime> {
         self.time
     }
+
+    pub fn duration(&self) -> Option<f32> {
+        self.duration
+    }
+
+    pub fn obstacles_passed(&self) -> Option<u32> {
+        self.obstacles_passed
+    }
+
+    pub fn max_speed(&self) -> Option<f32> {
+        self.max_speed
+    }
+
+    pub fn rules(&self) -> Option<&str> {
+        self.rules.as_deref()
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden == 1
+    }
 }
 
 pub struct Message {
@@ -235,7 +542,29 @@ pub struct Message {
     text: String,
 }
 
+static MESSAGE_RATE_LIMIT: OnceLock<RateLimit> = OnceLock::new();
+
+// At most `limit` messages can be sent in any `window_seconds`
+pub struct RateLimit {
+    pub limit: i64,
+    pub window_seconds: i64,
+}
+
 impl Message {
+    pub const MAX_LENGTH: usize = 500;
+
+    pub fn init_rate_limit(rate_limit: RateLimit) {
+        MESSAGE_RATE_LIMIT
+            .set(rate_limit)
+            .unwrap_or_else(|_| panic!("Message rate limit already set!"));
+    }
+
+    pub fn rate_limit() -> &'static RateLimit {
+        MESSAGE_RATE_LIMIT
+            .get()
+            .expect("Message rate limit not set!")
+    }
+
     pub fn new(
         from_user_id: u32,
         to_user_id: u32,
@@ -266,6 +595,41 @@ impl Message {
         Ok(())
     }
 
+    pub async fn fetch(id: u32, pool: &MySqlPool) -> Result<Option<Message>, sqlx::Error> {
+        sqlx::query_as!(Message, "SELECT * FROM messages WHERE id = ?", id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    // Removes the message for both sides, unlike hiding it
+    pub async fn delete(id: u32, pool: &MySqlPool) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM messages WHERE id = ?", id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    pub fn is_valid_text(text: &str) -> bool {
+        text::length(text) <= Self::MAX_LENGTH && text::is_well_formed_text(text)
+    }
+
+    // Messages the user sent within the rate limit window
+    pub async fn count_recent(from_user_id: u32, pool: &MySqlPool) -> Result<i64, sqlx::Error> {
+        let since = chrono::DateTime::from_timestamp_millis(
+            chrono::Utc::now().timestamp_millis() - Self::rate_limit().window_seconds * 1000,
+        )
+        .unwrap()
+        .naive_utc();
+        Ok(sqlx::query!(
+            "SELECT COUNT(*) as count FROM messages WHERE from_user_id = ? AND time > ?",
+            from_user_id,
+            since
+        )
+        .fetch_one(pool)
+        .await?
+        .count)
+    }
+
     pub async fn sender_username(&self, pool: &MySqlPool) -> Result<String, sqlx::Error> {
         User::username_from_id(self.from_user_id, pool).await
     }
@@ -278,6 +642,14 @@ impl Message {
         self.id
     }
 
+    pub fn from_user_id(&self) -> u32 {
+        self.from_user_id
+    }
+
+    pub fn to_user_id(&self) -> u32 {
+        self.to_user_id
+    }
+
     pub fn show_sender(&self) -> bool {
         self.show_sender == 1
     }
@@ -312,3 +684,227 @@ impl Block {
         Ok(())
     }
 }
+
+pub struct Replay {
+    pub run_id: u32,
+    pub data: String,
+}
+
+impl Replay {
+    // Replays are a few kilobytes for most runs
+    pub const MAX_SIZE: usize = 1024 * 1024;
+
+    pub async fn save(&self, pool: &MySqlPool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO replays(run_id, data) VALUES(?, ?)",
+            self.run_id,
+            self.data
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn fetch(run_id: u32, pool: &MySqlPool) -> Result<Option<Replay>, sqlx::Error> {
+        sqlx::query_as!(
+            Replay,
+            "SELECT run_id, data FROM replays WHERE run_id = ?",
+            run_id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+}
+
+pub struct Report {
+    id: u32,
+    reporter_id: u32,
+    target_kind: String,
+    target_id: u32,
+    reason: String,
+    time: chrono::NaiveDateTime,
+}
+
+impl Report {
+    pub const MAX_REASON_LENGTH: usize = 500;
+
+    pub fn new(reporter_id: u32, target_kind: &str, target_id: u32, reason: String) -> Self {
+        Self {
+            id: 0,
+            reporter_id,
+            target_kind: target_kind.to_string(),
+            target_id,
+            reason,
+            time: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn is_valid_reason(reason: &str) -> bool {
+        text::length(reason) <= Self::MAX_REASON_LENGTH && text::is_well_formed_text(reason)
+    }
+
+    pub async fn save(&self, pool: &MySqlPool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO reports(reporter_id, target_kind, target_id, reason, time) VALUES(?, ?, ?, ?, ?)",
+            self.reporter_id,
+            self.target_kind,
+            self.target_id,
+            self.reason,
+            self.time
+        )
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    // Whether the reporter already has an open report of the same target
+    pub async fn is_duplicate(&self, pool: &MySqlPool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"SELECT "x" FROM reports WHERE reporter_id = ? AND target_kind = ? AND target_id = ? AND resolution IS NULL"#,
+            self.reporter_id,
+            self.target_kind,
+            self.target_id
+        )
+            .fetch_optional(pool)
+            .await?;
+        Ok(result.is_some())
+    }
+
+    pub async fn fetch_open(id: u32, pool: &MySqlPool) -> Result<Option<Report>, sqlx::Error> {
+        sqlx::query_as!(
+            Report,
+            "SELECT id, reporter_id, target_kind, target_id, reason, time FROM reports WHERE id = ? AND resolution IS NULL",
+            id
+        )
+            .fetch_optional(pool)
+            .await
+    }
+
+    // The moderation queue, oldest first
+    pub async fn fetch_all_open(pool: &MySqlPool) -> Result<Vec<Report>, sqlx::Error> {
+        sqlx::query_as!(
+            Report,
+            "SELECT id, reporter_id, target_kind, target_id, reason, time FROM reports WHERE resolution IS NULL ORDER BY id"
+        )
+            .fetch_all(pool)
+            .await
+    }
+
+    // Closes every open report of the same target, not just this one
+    pub async fn resolve(
+        &self,
+        resolution: &str,
+        resolver_id: u32,
+        pool: &MySqlPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE reports SET resolution = ?, resolver_id = ?, resolved_at = ? WHERE target_kind = ? AND target_id = ? AND resolution IS NULL",
+            resolution,
+            resolver_id,
+            chrono::Utc::now().naive_utc(),
+            self.target_kind,
+            self.target_id
+        )
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn reporter_username(&self, pool: &MySqlPool) -> Result<String, sqlx::Error> {
+        User::username_from_id(self.reporter_id, pool).await
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn target_kind(&self) -> &str {
+        &self.target_kind
+    }
+
+    pub fn target_id(&self) -> u32 {
+        self.target_id
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    pub fn time(&self) -> chrono::NaiveDateTime {
+        self.time
+    }
+}
+
+pub struct AuditEntry {
+    id: u32,
+    // Missing once the moderator's account is deleted
+    actor_id: Option<u32>,
+    action: String,
+    target_kind: String,
+    target_id: u32,
+    time: chrono::NaiveDateTime,
+}
+
+impl AuditEntry {
+    pub fn new(actor_id: u32, action: &str, target_kind: &str, target_id: u32) -> Self {
+        Self {
+            id: 0,
+            actor_id: Some(actor_id),
+            action: action.to_string(),
+            target_kind: target_kind.to_string(),
+            target_id,
+            time: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub async fn save(&self, pool: &MySqlPool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO audit_log(actor_id, action, target_kind, target_id, time) VALUES(?, ?, ?, ?, ?)",
+            self.actor_id,
+            self.action,
+            self.target_kind,
+            self.target_id,
+            self.time
+        )
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    // Newest first
+    pub async fn fetch_all(pool: &MySqlPool) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        sqlx::query_as!(
+            AuditEntry,
+            r#"SELECT id, actor_id as "actor_id?", action, target_kind, target_id, time FROM audit_log ORDER BY id DESC"#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn actor_username(&self, pool: &MySqlPool) -> Result<Option<String>, sqlx::Error> {
+        match self.actor_id {
+            Some(id) => Ok(Some(User::username_from_id(id, pool).await?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+
+    pub fn target_kind(&self) -> &str {
+        &self.target_kind
+    }
+
+    pub fn target_id(&self) -> u32 {
+        self.target_id
+    }
+
+    pub fn time(&self) -> chrono::NaiveDateTime {
+        self.time
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+use std::fs;
+use std::sync::OnceLock;
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+
+use crate::text;
+
+static POLICY: OnceLock<PasswordPolicy> = OnceLock::new();
+static RECOVERY_CODE_SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    // Lowercase, checked without regard to case
+    common_passwords: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    // The common password list has one password per line, empty lines and lines starting with #
+    // are skipped
+    pub fn load(min_length: usize, max_length: usize, common_passwords_path: &str) -> Self {
+        let common_passwords = match fs::read_to_string(common_passwords_path) {
+            Ok(list) => list
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect(),
+            Err(error) => {
+                tide::log::warn!(
+                    "Couldn't read the common password list {}: {}",
+                    common_passwords_path,
+                    error
+                );
+                HashSet::new()
+            }
+        };
+        Self {
+            min_length,
+            max_length,
+            common_passwords,
+        }
+    }
+}
+
+pub fn init_policy(policy: PasswordPolicy) {
+    POLICY
+        .set(policy)
+        .unwrap_or_else(|_| panic!("Password policy already set!"));
+}
+
+// Explains what's wrong with the password so the player can pick a better one
+pub fn check(password: &str, username: &str) -> Result<(), String> {
+    let policy = POLICY.get().expect("Password policy not set!");
+    let length = text::length(password);
+    if length < policy.min_length {
+        Err(format!(
+            "Password needs to contain at least {} characters.",
+            policy.min_length
+        ))
+    } else if length > policy.max_length {
+        Err(format!(
+            "Password needs to contain at most {} characters.",
+            policy.max_length
+        ))
+    } else if policy.common_passwords.contains(&password.to_lowercase()) {
+        Err("This password is too common, pick a different one.".to_string())
+    } else if text::username_key(password) == text::username_key(username) {
+        Err("Password can't be the same as the username.".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+// Argon2id with the recommended parameters, hashes made with anything else get replaced at login
+fn hasher() -> Argon2<'static> {
+    Argon2::default()
+}
+
+pub fn hash(password: &str) -> String {
+    let salt = SaltString::generate(&mut rand::rngs::OsRng);
+    hasher()
+        .hash_password(password.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
+}
+
+fn is_bcrypt(hash: &str) -> bool {
+    hash.starts_with("$2")
+}
+
+// Also accepts the bcrypt hashes all passwords were stored with before
+pub fn verify(password: &str, hash: &str) -> bool {
+    if is_bcrypt(hash) {
+        return bcrypt::verify(password, hash).unwrap_or(false);
+    }
+    PasswordHash::new(hash)
+        .is_ok_and(|hash| hasher().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+pub fn needs_rehash(hash: &str) -> bool {
+    if is_bcrypt(hash) {
+        return true;
+    }
+    let Ok(hash) = PasswordHash::new(hash) else {
+        return true;
+    };
+    let current = hasher().params().clone();
+    hash.algorithm != Algorithm::Argon2id.ident()
+        || !Params::try_from(&hash).is_ok_and(|params| {
+            params.m_cost() == current.m_cost()
+                && params.t_cost() == current.t_cost()
+                && params.p_cost() == current.p_cost()
+        })
+}
+
+const RECOVERY_CODE_COUNT: usize = 8;
+const RECOVERY_CODE_LENGTH: usize = 10;
+// No 0, 1, i, l or o, which are easy to mix up when written down
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+// Shown to the player as xxxxx-xxxxx, each can reset the password once
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code: String = (0..RECOVERY_CODE_LENGTH)
+                .map(|_| {
+                    RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char
+                })
+                .collect();
+            format!(
+                "{}-{}",
+                &code[..RECOVERY_CODE_LENGTH / 2],
+                &code[RECOVERY_CODE_LENGTH / 2..]
+            )
+        })
+        .collect()
+}
+
+// Codes are typed in by hand, so case, dashes and spaces don't matter
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// Without a configured secret codes are hashed with an empty key, they keep working but a leaked
+// database is enough to guess them
+pub fn init_recovery_code_secret(secret: Option<String>) {
+    let secret = match secret {
+        Some(secret) => secret.into_bytes(),
+        None => {
+            tide::log::warn!("RECOVERY_CODE_SECRET not set, hashing recovery codes without a key");
+            Vec::new()
+        }
+    };
+    RECOVERY_CODE_SECRET
+        .set(secret)
+        .expect("Recovery code secret already set!");
+}
+
+// Codes are random, unlike passwords, so a fast keyed hash is enough and they can be looked up by it
+pub fn hash_recovery_code(code: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(
+        RECOVERY_CODE_SECRET
+            .get()
+            .expect("Recovery code secret not set!"),
+    )
+    .expect("HMAC takes keys of any size");
+    mac.update(normalize_recovery_code(code).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
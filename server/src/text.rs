@@ -0,0 +1,73 @@
+use serde::{Deserialize, Deserializer};
+use unicode_normalization::UnicodeNormalization;
+use unicode_properties::emoji::{is_tag_character, is_zwj};
+use unicode_properties::{GeneralCategory, UnicodeEmoji, UnicodeGeneralCategory};
+use unicode_segmentation::UnicodeSegmentation;
+
+// Enough for the longest emoji sequences like families and subdivision flags, stops piles of
+// combining marks
+const MAX_CHARS_PER_GRAPHEME: usize = 8;
+
+// Usernames are stored in NFKC so lookalike forms like fullwidth letters end up the same
+pub fn normalize_username(username: &str) -> String {
+    username.nfkc().collect()
+}
+
+pub fn deserialize_username<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<String, D::Error> {
+    Ok(normalize_username(&String::deserialize(deserializer)?))
+}
+
+// Usernames that only differ in case or compatibility forms share a key and can't both exist
+pub fn username_key(username: &str) -> String {
+    username
+        .nfkc()
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .nfkc()
+        .collect()
+}
+
+// Characters as the player sees and counts them
+pub fn length(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+// Format characters are only allowed inside emoji sequences, where they join emoji (ZWJ) or
+// spell out subdivision flags (tags)
+fn is_visible_grapheme(grapheme: &str, allow_line_breaks: bool) -> bool {
+    let in_emoji = grapheme
+        .chars()
+        .next()
+        .is_some_and(|c| !c.is_ascii() && c.is_emoji_char());
+    grapheme.chars().count() <= MAX_CHARS_PER_GRAPHEME
+        && grapheme.chars().all(|c| match c.general_category() {
+            GeneralCategory::Control => allow_line_breaks && (c == '\n' || c == '\r'),
+            GeneralCategory::Format => in_emoji && (is_zwj(c) || is_tag_character(c)),
+            GeneralCategory::SpaceSeparator => c == ' ',
+            GeneralCategory::LineSeparator
+            | GeneralCategory::ParagraphSeparator
+            | GeneralCategory::Surrogate
+            | GeneralCategory::PrivateUse
+            | GeneralCategory::Unassigned => false,
+            _ => true,
+        })
+}
+
+// Single spaces between words only, so names can't be padded to look like someone else's
+pub fn is_well_formed_username(username: &str) -> bool {
+    !username.starts_with(' ')
+        && !username.ends_with(' ')
+        && !username.contains("  ")
+        && username
+            .graphemes(true)
+            .all(|grapheme| is_visible_grapheme(grapheme, false))
+}
+
+pub fn is_well_formed_text(text: &str) -> bool {
+    !text.trim().is_empty()
+        && text
+            .graphemes(true)
+            .all(|grapheme| is_visible_grapheme(grapheme, true))
+}
@@ -0,0 +1,201 @@
+use std::sync::OnceLock;
+
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+use sqlx::types::chrono;
+use sqlx::MySqlPool;
+
+type HmacSha256 = Hmac<Sha256>;
+
+static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+
+// Tickets are only good for one day of play
+const MAX_AGE_MILLIS: i64 = 24 * 60 * 60 * 1000;
+// More than the game allows even at top speed with the score multiplier running the whole time
+const MAX_POINTS_PER_SECOND: f32 = 12.0;
+// Time the game spends loading and redirecting that isn't part of the run
+const DURATION_SLACK_SECONDS: f32 = 5.0;
+
+// Without a configured secret tickets are signed with a random one and stop working on restart
+pub fn init_secret(secret: Option<String>) {
+    let secret = match secret {
+        Some(secret) => secret.into_bytes(),
+        None => {
+            tide::log::warn!("RUN_TICKET_SECRET not set, using a random secret");
+            rand::thread_rng().gen::<[u8; 32]>().to_vec()
+        }
+    };
+    SECRET.set(secret).expect("Secret already set!");
+}
+
+fn mac() -> HmacSha256 {
+    HmacSha256::new_from_slice(SECRET.get().expect("Secret not set!"))
+        .expect("HMAC takes keys of any size")
+}
+
+// Issued when a game starts, the run it ends in can only be submitted with it and only once
+pub struct RunTicket {
+    pub user_id: u32,
+    pub seed: u32,
+    pub difficulty: String,
+    issued_at: i64,
+    nonce: String,
+}
+
+impl RunTicket {
+    // Random seed unless the run has to be played with a given one, the difficulty has to be valid
+    pub fn issue(user_id: u32, seed: Option<u32>, difficulty: &str) -> Self {
+        let mut rng = rand::thread_rng();
+        Self {
+            user_id,
+            seed: seed.unwrap_or_else(|| rng.gen()),
+            difficulty: difficulty.to_string(),
+            issued_at: chrono::Utc::now().timestamp_millis(),
+            nonce: hex::encode(rng.gen::<[u8; 16]>()),
+        }
+    }
+
+    fn payload(&self) -> String {
+        format!(
+            "{}.{}.{}.{}.{}",
+            self.user_id, self.seed, self.difficulty, self.issued_at, self.nonce
+        )
+    }
+
+    pub fn encode(&self) -> String {
+        let payload = self.payload();
+        let mut mac = mac();
+        mac.update(payload.as_bytes());
+        format!("{}.{}", payload, hex::encode(mac.finalize().into_bytes()))
+    }
+
+    // None unless the ticket was issued by this server and hasn't been tampered with
+    pub fn decode(ticket: &str) -> Option<Self> {
+        let (payload, signature) = ticket.rsplit_once('.')?;
+        let mut mac = mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&hex::decode(signature).ok()?).ok()?;
+        let mut parts = payload.split('.');
+        let ticket = Self {
+            user_id: parts.next()?.parse().ok()?,
+            seed: parts.next()?.parse().ok()?,
+            difficulty: parts.next()?.to_string(),
+            issued_at: parts.next()?.parse().ok()?,
+            nonce: parts.next()?.to_string(),
+        };
+        parts.next().is_none().then_some(ticket)
+    }
+
+    fn elapsed_millis(&self) -> i64 {
+        chrono::Utc::now().timestamp_millis() - self.issued_at
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.elapsed_millis() > MAX_AGE_MILLIS
+    }
+
+    // Whether a run with this score and reported duration fits in the time since the ticket was issued
+    pub fn is_plausible(&self, score: u32, duration: Option<f32>) -> bool {
+        let elapsed = self.elapsed_millis() as f32 / 1000.0;
+        let duration = duration.unwrap_or(elapsed);
+        duration <= elapsed + DURATION_SLACK_SECONDS
+            && score as f32 <= duration * MAX_POINTS_PER_SECOND
+    }
+
+    // Marks the ticket as used, returns false when it already was
+    pub async fn redeem(&self, pool: &MySqlPool) -> Result<bool, sqlx::Error> {
+        let now = chrono::Utc::now().naive_utc();
+        // Tickets issued before then have expired and get turned down before redeeming, so their
+        // nonces don't have to be remembered anymore
+        sqlx::query!(
+            "DELETE FROM used_run_tickets WHERE used_at < ?",
+            now - chrono::Duration::milliseconds(MAX_AGE_MILLIS)
+        )
+        .execute(pool)
+        .await?;
+        let result = sqlx::query!(
+            "INSERT INTO used_run_tickets(nonce, user_id, used_at) VALUES(?, ?, ?)",
+            self.nonce,
+            self.user_id,
+            now
+        )
+        .execute(pool)
+        .await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(sqlx::Error::Database(error)) if error.is_unique_violation() => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticket() -> RunTicket {
+        SECRET.get_or_init(|| b"test secret".to_vec());
+        RunTicket::issue(7, Some(1234), "hard")
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let ticket = ticket();
+        let decoded = RunTicket::decode(&ticket.encode()).unwrap();
+        assert_eq!(decoded.user_id, 7);
+        assert_eq!(decoded.seed, 1234);
+        assert_eq!(decoded.difficulty, "hard");
+        assert_eq!(decoded.issued_at, ticket.issued_at);
+        assert_eq!(decoded.nonce, ticket.nonce);
+    }
+
+    #[test]
+    fn rejects_tampered_tickets() {
+        let encoded = ticket().encode();
+        let (payload, signature) = encoded.rsplit_once('.').unwrap();
+        for tampered in [
+            encoded.replacen("7.", "8.", 1),
+            encoded.replace(".1234.", ".1235."),
+            encoded.replace(".hard.", ".easy."),
+            format!("{}.{}", payload, "0".repeat(signature.len())),
+            format!("{}.extra.{}", payload, signature),
+            payload.to_string(),
+            String::new(),
+        ] {
+            assert!(RunTicket::decode(&tampered).is_none(), "{}", tampered);
+        }
+    }
+
+    #[test]
+    fn rejects_tickets_signed_with_another_secret() {
+        let ticket = ticket();
+        let mut mac = HmacSha256::new_from_slice(b"another secret").unwrap();
+        mac.update(ticket.payload().as_bytes());
+        let forged = format!(
+            "{}.{}",
+            ticket.payload(),
+            hex::encode(mac.finalize().into_bytes())
+        );
+        assert!(RunTicket::decode(&forged).is_none());
+    }
+
+    #[test]
+    fn checks_plausibility() {
+        let mut ticket = ticket();
+        ticket.issued_at -= 60_000;
+        assert!(ticket.is_plausible(300, Some(55.0)));
+        assert!(ticket.is_plausible(300, None));
+        assert!(!ticket.is_plausible(300, Some(10.0)));
+        assert!(!ticket.is_plausible(1000, None));
+        assert!(!ticket.is_plausible(0, Some(120.0)));
+    }
+
+    #[test]
+    fn expires() {
+        let mut ticket = ticket();
+        assert!(!ticket.is_expired());
+        ticket.issued_at -= MAX_AGE_MILLIS + 1;
+        assert!(ticket.is_expired());
+    }
+}
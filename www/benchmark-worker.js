@@ -0,0 +1,20 @@
+import * as game from './game.js'
+
+// Rayon blocks the thread that hands it work, which the browser only allows in a worker
+const ready = (async () => {
+    await game.default('./game_bg.wasm')
+    // Only present in parallel builds, and shared memory needs a cross-origin isolated page
+    if (game.initThreadPool && self.crossOriginIsolated) {
+        await game.initThreadPool(navigator.hardwareConcurrency)
+    }
+})()
+
+onmessage = async event => {
+    await ready
+    let { frames, width, height } = event.data
+    try {
+        postMessage(await game.benchmark(frames, width, height))
+    } catch (error) {
+        postMessage("Benchmark failed: " + error)
+    }
+}
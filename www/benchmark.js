@@ -0,0 +1,13 @@
+window.onload = () => {
+    let worker = new Worker("benchmark-worker.js", { type: "module" })
+    let output = document.getElementById("benchmark_output")
+    worker.onmessage = event => {
+        output.innerText = event.data
+    }
+    document.getElementById("run_benchmark").onclick = () => {
+        output.innerText = "Running..."
+        let width = Math.floor(window.screen.width * window.devicePixelRatio)
+        let height = Math.floor(window.screen.height * window.devicePixelRatio)
+        worker.postMessage({ frames: 100, width, height })
+    }
+}
@@ -0,0 +1,41 @@
+const defaultBindings = {
+    move_left: ["KeyA", "ArrowLeft"],
+    move_right: ["KeyD", "ArrowRight"],
+    pause: ["Escape", "KeyP"],
+}
+
+function loadBindings() {
+    let stored = localStorage.getItem("key bindings")
+    // Bindings stored before an action existed fall back to its defaults
+    return Object.assign(structuredClone(defaultBindings), stored === null ? {} : JSON.parse(stored))
+}
+
+function showBindings(bindings) {
+    for (let action in bindings) {
+        document.getElementById(action + "_keys").innerText = bindings[action].join(", ")
+    }
+}
+
+window.onload = () => {
+    let bindings = loadBindings()
+    showBindings(bindings)
+
+    for (let action in defaultBindings) {
+        document.getElementById("rebind_" + action).onclick = () => {
+            document.getElementById(action + "_keys").innerText = "Press a key..."
+            window.addEventListener("keydown", event => {
+                event.preventDefault()
+                // The new key replaces the primary binding, the secondary one stays
+                bindings[action] = [event.code].concat(bindings[action].slice(1))
+                localStorage.setItem("key bindings", JSON.stringify(bindings))
+                showBindings(bindings)
+            }, {once: true})
+        }
+    }
+
+    document.getElementById("reset_bindings").onclick = () => {
+        localStorage.removeItem("key bindings")
+        bindings = loadBindings()
+        showBindings(bindings)
+    }
+}
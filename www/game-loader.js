@@ -1,5 +1,59 @@
-import {default as init} from './game.js';
+import * as game from './game.js';
+
+function setUpEffectToggles() {
+    for (let checkbox of document.querySelectorAll("input[data-effect]")) {
+        let effect = checkbox.dataset.effect
+        let stored = localStorage.getItem("effect " + effect)
+        if (stored !== null) {
+            checkbox.checked = stored === "true"
+        }
+        game.setEffectEnabled(effect, checkbox.checked)
+        checkbox.onchange = () => {
+            localStorage.setItem("effect " + effect, checkbox.checked)
+            game.setEffectEnabled(effect, checkbox.checked)
+            // Keep keyboard input going to the game
+            document.getElementById("render_target").focus()
+        }
+    }
+}
+
+// Submitting a run needs a ticket from the server issued before the game started, the game plays with
+// the seed the ticket was issued for, which is the ghost's one when racing
+async function startRun() {
+    sessionStorage.removeItem("run ticket");
+    sessionStorage.removeItem("run seed");
+    let race = sessionStorage.getItem("ghost run");
+    sessionStorage.removeItem("ghost run");
+    if (sessionStorage.getItem("logged in") !== "true" || sessionStorage.getItem("watch replay") !== null) {
+        return;
+    }
+    let response = await fetch("start-run", {
+        method: "POST",
+        headers: {
+            "Accept": "application/json",
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify({
+            user: {
+                username: sessionStorage.getItem("username"),
+                password: sessionStorage.getItem("password")
+            },
+            difficulty: sessionStorage.getItem("difficulty") ?? "normal",
+            race: race === null ? null : parseInt(race)
+        })
+    })
+        .then(response => response.text())
+        .then(text => JSON.parse(text))
+        .catch(() => null);
+    if (response !== null && response.code === 0) {
+        sessionStorage.setItem("run ticket", response.body.ticket);
+        sessionStorage.setItem("run seed", response.body.seed);
+    }
+}
 
 window.onload = async () => {
-    await init('./game_bg.wasm');
+    await game.default('./game_bg.wasm');
+    setUpEffectToggles();
+    await startRun();
+    await game.run();
 }
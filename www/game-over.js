@@ -20,8 +20,12 @@ function submitRun() {
                     password: sessionStorage.getItem("password")
                 },
                 run: {
-                    score: parseInt(sessionStorage.getItem("score"))
-                }
+                    score: parseInt(sessionStorage.getItem("score")),
+                    difficulty: sessionStorage.getItem("difficulty") ?? "normal",
+                    ...JSON.parse(sessionStorage.getItem("run stats") ?? "{}")
+                },
+                replay: JSON.parse(sessionStorage.getItem("last replay")),
+                ticket: sessionStorage.getItem("run ticket") ?? ""
             })
         })
             .then(response => response.text())
@@ -29,6 +33,7 @@ function submitRun() {
                 let response = JSON.parse(text)
                 if (response.code === 0) {
                     sessionStorage.removeItem("score")
+                    sessionStorage.removeItem("run ticket")
                     location.replace("hall-of-fame.html")
                 } else {
                     alert(response.body)
@@ -1,5 +1,9 @@
 import {default as fillTable} from "./score-table.js"
 
 window.onload = async () => {
-    await fillTable(document.getElementById("score_table"))
+    let table = document.getElementById("score_table")
+    let difficulty = document.getElementById("difficulty")
+    difficulty.value = sessionStorage.getItem("difficulty") ?? "normal"
+    difficulty.onchange = async () => await fillTable(table, null, difficulty.value)
+    await fillTable(table, null, difficulty.value)
 }
\ No newline at end of file
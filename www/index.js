@@ -1,4 +1,8 @@
 window.onload = () => {
+    let difficulty = document.getElementById("difficulty")
+    difficulty.value = sessionStorage.getItem("difficulty") ?? "normal"
+    difficulty.onchange = () => sessionStorage.setItem("difficulty", difficulty.value)
+    sessionStorage.setItem("difficulty", difficulty.value)
     let buttonBox = document.getElementById("button_box")
     if (sessionStorage.getItem("logged in") === "true") {
         let myRuns = document.createElement("a")
@@ -19,11 +23,20 @@ window.onload = () => {
         manageAccount.href = "manage-account.html"
         manageAccount.innerText = "Manage account"
         buttonBox.appendChild(manageAccount)
+        let role = sessionStorage.getItem("role")
+        if (role === "moderator" || role === "admin") {
+            let moderation = document.createElement("a")
+            moderation.className = "button"
+            moderation.href = "moderation.html"
+            moderation.innerText = "Moderation"
+            buttonBox.appendChild(moderation)
+        }
         let logOut = document.createElement("a")
         logOut.className = "button"
         logOut.innerText = "Log out"
         logOut.onclick = () => {
             sessionStorage.removeItem("logged in")
+            sessionStorage.removeItem("role")
             location.reload()
         }
         buttonBox.appendChild(logOut)
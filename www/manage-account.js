@@ -3,7 +3,7 @@ function changeUsername() {
 
     if (newUsername === "") {
         alert("New username cannot be empty!")
-    } else if (newUsername.length > 20) {
+    } else if (textLength(newUsername) > 20) {
         alert("New username can contain at most 20 characters.")
     } else {
         let body = {
@@ -36,10 +36,9 @@ function changeUsername() {
 function changePassword() {
     let newPassword = document.getElementById("newPassword").value
 
-    if (newPassword.length < 8) {
-        alert("New password needs to contain at least 8 characters.")
-    } else if (newPassword.length > 50) {
-        alert("New password needs to contain at most 50 characters.")
+    // Length and the other rules are up to the server's password policy
+    if (newPassword === "") {
+        alert("New password cannot be empty!")
     } else {
         let repeatNewPassword = document.getElementById("repeatNewPassword").value
 
@@ -76,6 +75,30 @@ function changePassword() {
     }
 }
 
+// The old codes stop working
+function regenerateRecoveryCodes() {
+    let password = document.getElementById("recoveryPassword").value
+
+    fetch("regenerate-recovery-codes", {
+        method: "POST",
+        headers: {
+            "Accept": "application/json",
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify({username: sessionStorage.getItem("username"), password})
+    })
+        .then(response => response.text())
+        .then(text => {
+            let response = JSON.parse(text)
+            if (response.code === 0) {
+                sessionStorage.setItem("recovery codes", JSON.stringify(response.body))
+                location.assign("recovery-codes.html")
+            } else {
+                alert(response.body)
+            }
+        })
+}
+
 function deleteAccount() {
     let password = document.getElementById("deletePassword").value
 
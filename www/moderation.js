@@ -0,0 +1,181 @@
+window.onload = async () => {
+    let difficulty = document.getElementById("difficulty")
+    difficulty.value = sessionStorage.getItem("difficulty") ?? "normal"
+    difficulty.onchange = async () => await fillRunTable()
+    await fillReportTable()
+    await fillRunTable()
+    if (sessionStorage.getItem("role") === "admin") {
+        await fillAuditLogTable()
+    } else {
+        document.getElementById("audit_log_box").remove()
+    }
+}
+
+function currentUser() {
+    return {
+        username: sessionStorage.getItem("username"),
+        password: sessionStorage.getItem("password")
+    }
+}
+
+async function moderationRequest(endpoint, method, body) {
+    let response = await fetch(`moderation/${endpoint}`, {
+        method,
+        headers: {
+            "Accept": "application/json",
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify(body)
+    })
+    response = JSON.parse(await response.text())
+    if (response.code === 0) {
+        return response.body
+    } else {
+        alert(response.body)
+    }
+}
+
+function headerRow(names) {
+    let thead = document.createElement("thead")
+    let tr = document.createElement("tr")
+    for (let name of names) {
+        let th = document.createElement("th")
+        th.innerText = name
+        tr.appendChild(th)
+    }
+    thead.appendChild(tr)
+    return thead
+}
+
+function cell(row, text) {
+    let td = document.createElement("td")
+    td.innerText = text
+    row.appendChild(td)
+    return td
+}
+
+function actionLink(parent, text, onclick) {
+    let link = document.createElement("a")
+    link.innerText = text
+    link.style.cursor = "pointer"
+    link.onclick = onclick
+    parent.appendChild(link)
+    parent.append(" ")
+}
+
+async function fillReportTable() {
+    let table = document.getElementById("report_table")
+    table.innerText = ""
+    table.appendChild(headerRow(["Time", "Reporter", "Reported", "Content", "Reason", "Actions"]))
+
+    let reports = await moderationRequest("get-reports", "POST", currentUser()) ?? []
+    for (let report of reports) {
+        let row = document.createElement("tr")
+        cell(row, report.time)
+        cell(row, report.reporter)
+        cell(row, `${report.targetKind} of ${report.offender ?? "(deleted)"}`)
+        cell(row, report.content ?? "(deleted)")
+        cell(row, report.reason)
+        let actions = cell(row, "")
+        let resolve = async resolution => {
+            let result = await moderationRequest("resolve-report", "PUT", {
+                user: currentUser(),
+                report_id: report.id,
+                resolution
+            })
+            if (result !== undefined) {
+                await fillReportTable()
+                await fillRunTable()
+            }
+        }
+        actionLink(actions, "Dismiss", () => resolve({action: "dismiss"}))
+        if (report.targetKind !== "user") {
+            actionLink(actions, "Delete", () => resolve({action: "delete"}))
+        }
+        actionLink(actions, "Suspend", () => {
+            let days = prompt(`Suspend ${report.offender} for how many days?`, "7")
+            if (days !== null) {
+                resolve({action: "suspend", days: parseInt(days)})
+            }
+        })
+        table.appendChild(row)
+    }
+}
+
+async function fillRunTable() {
+    let table = document.getElementById("run_table")
+    let difficulty = document.getElementById("difficulty").value
+    table.innerText = ""
+    table.appendChild(headerRow(["Username", "Score", "Time", "Duration", "Version", "Actions"]))
+
+    let runs = await moderationRequest("get-runs", "POST", {user: currentUser(), difficulty}) ?? []
+    runs.sort((a, b) => b.score - a.score)
+    for (let run of runs) {
+        let row = document.createElement("tr")
+        if (run.hidden) {
+            row.style.opacity = "0.5"
+        }
+        cell(row, run.username)
+        cell(row, run.score)
+        cell(row, run.time)
+        cell(row, run.duration === null ? "" : `${run.duration.toFixed(1)} s`)
+        cell(row, run.version ?? "")
+        let actions = cell(row, "")
+        actionLink(actions, run.hidden ? "Unhide" : "Hide", async () => {
+            let result = await moderationRequest("hide-run", "PUT", {
+                user: currentUser(),
+                run_id: run.id,
+                hidden: !run.hidden
+            })
+            if (result !== undefined) {
+                await fillRunTable()
+            }
+        })
+        if (sessionStorage.getItem("role") === "admin") {
+            actionLink(actions, "Delete", async () => {
+                if (!confirm(`Delete the run of ${run.username} with score ${run.score}?`)) {
+                    return
+                }
+                let result = await moderationRequest("delete-run", "DELETE", {
+                    user: currentUser(),
+                    run_id: run.id
+                })
+                if (result !== undefined) {
+                    await fillRunTable()
+                }
+            })
+        }
+        table.appendChild(row)
+    }
+}
+
+async function fillAuditLogTable() {
+    let table = document.getElementById("audit_log_table")
+    table.innerText = ""
+    table.appendChild(headerRow(["Time", "Moderator", "Action", "Target"]))
+
+    let entries = await moderationRequest("get-audit-log", "POST", currentUser()) ?? []
+    for (let entry of entries) {
+        let row = document.createElement("tr")
+        cell(row, entry.time)
+        cell(row, entry.actor ?? "(deleted)")
+        cell(row, entry.action)
+        cell(row, `${entry.targetKind} ${entry.targetId}`)
+        table.appendChild(row)
+    }
+}
+
+async function banUser(banned) {
+    let username = document.getElementById("banUsername").value
+    let result = await moderationRequest("ban-user", "PUT", {
+        user: currentUser(),
+        target: {username},
+        banned
+    })
+    if (result !== undefined) {
+        alert(banned ? `${username} was banned.` : `${username} was unbanned.`)
+        if (sessionStorage.getItem("role") === "admin") {
+            await fillAuditLogTable()
+        }
+    }
+}
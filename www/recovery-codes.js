@@ -0,0 +1,24 @@
+window.onload = () => {
+    let codes = JSON.parse(sessionStorage.getItem("recovery codes") ?? "[]")
+    let list = document.getElementById("recovery_codes")
+    for (const code of codes) {
+        list.append(code)
+        list.appendChild(document.createElement("br"))
+    }
+}
+
+function downloadRecoveryCodes() {
+    let codes = JSON.parse(sessionStorage.getItem("recovery codes") ?? "[]")
+    let text = `Cube Dodger recovery codes for ${sessionStorage.getItem("username")}\n\n${codes.join("\n")}\n`
+    let link = document.createElement("a")
+    link.href = URL.createObjectURL(new Blob([text], {type: "text/plain"}))
+    link.download = "cube-dodger-recovery-codes.txt"
+    link.click()
+    URL.revokeObjectURL(link.href)
+}
+
+// The codes aren't kept any longer than it takes to write them down
+function finish() {
+    sessionStorage.removeItem("recovery codes")
+    location.replace("/")
+}
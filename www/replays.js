@@ -0,0 +1,71 @@
+function watchReplay(replay) {
+    sessionStorage.setItem("watch replay", replay)
+    location.assign("game.html")
+}
+
+// The race is played with the ghost's seed and rules, so it's on the same difficulty
+function raceReplay(replay, runId) {
+    sessionStorage.setItem("ghost replay", replay)
+    sessionStorage.setItem("ghost run", runId)
+    sessionStorage.setItem("difficulty", JSON.parse(replay).rules.difficulty)
+    location.assign("game.html")
+}
+
+function downloadReplay(replay, name) {
+    let link = document.createElement("a")
+    link.href = URL.createObjectURL(new Blob([replay], {type: "application/json"}))
+    link.download = name
+    link.click()
+    URL.revokeObjectURL(link.href)
+}
+
+async function watchReplayFile() {
+    let file = document.getElementById("replay_file").files[0]
+    if (file === undefined) {
+        alert("Choose a replay file first.")
+    } else {
+        watchReplay(await file.text())
+    }
+}
+
+async function fetchReplay(runId) {
+    return await fetch("get-replay", {
+        method: "POST",
+        headers: {
+            "Accept": "application/json",
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify({run_id: runId})
+    })
+        .then(response => response.text())
+        .then(text => {
+            let response = JSON.parse(text)
+            if (response.code === 0) {
+                return JSON.stringify(response.body)
+            } else {
+                alert(response.body)
+            }
+        })
+}
+
+async function raceTopRun(difficulty) {
+    let runs = await fetch("get-runs", {
+        method: "POST",
+        headers: {
+            "Accept": "application/json",
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify({difficulty})
+    })
+        .then(response => response.text())
+        .then(text => JSON.parse(text).body)
+    if (!Array.isArray(runs) || runs.length === 0) {
+        alert("There are no runs to race on this difficulty yet.")
+        return
+    }
+    let best = runs.reduce((best, run) => run.score > best.score ? run : best)
+    let replay = await fetchReplay(best.id)
+    if (replay !== undefined) {
+        raceReplay(replay, best.id)
+    }
+}
@@ -0,0 +1,41 @@
+// target is {kind: "message", id}, {kind: "run", id} or {kind: "user", username}
+function report(target) {
+    let username = sessionStorage.getItem("username")
+    if (sessionStorage.getItem("logged in") !== "true") {
+        alert("You need to be logged in to report.")
+        return
+    }
+    let reason = prompt("Why are you reporting this?")
+    if (reason === null) {
+        return
+    } else if (reason.trim() === "") {
+        alert("Give a reason for the report.")
+    } else if (textLength(reason) > 500) {
+        alert("Reason needs to be 500 characters or less.")
+    } else {
+        fetch("report", {
+            method: "POST",
+            headers: {
+                "Accept": "application/json",
+                "Content-Type": "application/json"
+            },
+            body: JSON.stringify({
+                user: {
+                    username,
+                    password: sessionStorage.getItem("password")
+                },
+                target,
+                reason
+            })
+        })
+            .then(response => response.text())
+            .then(text => {
+                let response = JSON.parse(text)
+                if (response.code === 0) {
+                    alert("Thanks, a moderator will look at your report.")
+                } else {
+                    alert(response.body)
+                }
+            })
+    }
+}
@@ -1,4 +1,4 @@
-async function fillTable(table, user = null) {
+async function fillTable(table, user = null, difficulty = "normal") {
     let thead = document.createElement("thead")
     let tr = document.createElement("tr")
     thead.appendChild(tr)
@@ -14,15 +14,22 @@ async function fillTable(table, user = null) {
     let time = document.createElement("th")
     time.innerText = "Time"
     tr.appendChild(time)
+    let replay = document.createElement("th")
+    replay.innerText = "Replay"
+    tr.appendChild(replay)
+    let loggedIn = sessionStorage.getItem("logged in") === "true"
+    if (loggedIn) {
+        tr.appendChild(document.createElement("th"))
+    }
 
     table.innerText = ""
     table.appendChild(thead)
 
-    let runs = await fetchRuns(user);
+    let runs = await fetchRuns(user, difficulty) ?? [];
     runs.sort((a, b) => b.score - a.score)
     let rankedUsers = new Set
     let current_place = 1;
-    let current_score = runs[0].score
+    let current_score = runs.length > 0 ? runs[0].score : 0
     let run_elements = runs.map((run) => {
         if (user !== null || !rankedUsers.has(run.username)) {
             if (run.score < current_score) {
@@ -53,6 +60,51 @@ async function fillTable(table, user = null) {
         let time = document.createElement("td")
         time.innerText = run.time
         run_element.appendChild(time)
+        let replay = document.createElement("td")
+        let watch = document.createElement("a")
+        watch.innerText = "Watch"
+        watch.style.cursor = "pointer"
+        watch.onclick = async () => {
+            let replay = await fetchReplay(run.id)
+            if (replay !== undefined) {
+                watchReplay(replay)
+            }
+        }
+        replay.appendChild(watch)
+        replay.append(" ")
+        let download = document.createElement("a")
+        download.innerText = "Download"
+        download.style.cursor = "pointer"
+        download.onclick = async () => {
+            let replay = await fetchReplay(run.id)
+            if (replay !== undefined) {
+                downloadReplay(replay, `replay-${run.id}.json`)
+            }
+        }
+        replay.appendChild(download)
+        replay.append(" ")
+        let race = document.createElement("a")
+        race.innerText = "Race"
+        race.style.cursor = "pointer"
+        race.onclick = async () => {
+            let replay = await fetchReplay(run.id)
+            if (replay !== undefined) {
+                raceReplay(replay, run.id)
+            }
+        }
+        replay.appendChild(race)
+        run_element.appendChild(replay)
+        if (loggedIn) {
+            let reportCell = document.createElement("td")
+            if (run.username !== sessionStorage.getItem("username")) {
+                let reportLink = document.createElement("a")
+                reportLink.innerText = "Report"
+                reportLink.style.cursor = "pointer"
+                reportLink.onclick = () => report({kind: "run", id: run.id})
+                reportCell.appendChild(reportLink)
+            }
+            run_element.appendChild(reportCell)
+        }
         return run_element
     })
 
@@ -63,13 +115,11 @@ async function fillTable(table, user = null) {
     table.appendChild(tbody)
 }
 
-async function fetchRuns(username = null) {
-    let body = {}
+async function fetchRuns(username = null, difficulty = "normal") {
+    let body = {difficulty}
     if (username !== null) {
-        body = {
-            user: {
-                username
-            }
+        body.user = {
+            username
         }
     }
     return await fetch("get-runs", {
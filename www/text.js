@@ -0,0 +1,4 @@
+// Counts characters the way the server does, an emoji or a letter with accents is one character
+function textLength(text) {
+    return [...new Intl.Segmenter().segment(text)].length
+}
@@ -12,12 +12,10 @@ function signUp() {
 
     if (username === "") {
         alert("Username cannot be empty!")
-    } else if (username.length > 20) {
+    } else if (textLength(username) > 20) {
         alert("Username can contain at most 20 characters.")
-    } else if (password.length < 8) {
-        alert("Password needs to contain at least 8 characters.")
-    } else if (password.length > 50) {
-        alert("Password needs to contain at most 50 characters.")
+    } else if (password === "") {
+        alert("Password cannot be empty!")
     } else if (password !== repeatPassword) {
         alert("Passwords don't match!")
     } else {
@@ -36,7 +34,9 @@ function signUp() {
                     sessionStorage.setItem("logged in", "true")
                     sessionStorage.setItem("username", username)
                     sessionStorage.setItem("password", password)
-                    location.replace("/")
+                    sessionStorage.setItem("role", "player")
+                    sessionStorage.setItem("recovery codes", JSON.stringify(response.body))
+                    location.replace("recovery-codes.html")
                 } else {
                     alert(response.body)
                 }
@@ -63,9 +63,44 @@ function logIn() {
                 sessionStorage.setItem("logged in", "true")
                 sessionStorage.setItem("username", username)
                 sessionStorage.setItem("password", password)
+                sessionStorage.setItem("role", response.body)
                 location.replace("/")
             } else {
                 alert(response.body)
             }
         })
 }
+
+function resetPassword() {
+    let username = document.getElementById("username").value
+    let recoveryCode = document.getElementById("recoveryCode").value
+    let newPassword = document.getElementById("password").value
+    let repeatPassword = document.getElementById("repeatPassword").value
+
+    if (username === "" || recoveryCode === "") {
+        alert("Fill in your username and a recovery code.")
+    } else if (newPassword === "") {
+        alert("Password cannot be empty!")
+    } else if (newPassword !== repeatPassword) {
+        alert("Passwords don't match!")
+    } else {
+        fetch("reset-password", {
+            method: "PUT",
+            headers: {
+                "Accept": "application/json",
+                "Content-Type": "application/json"
+            },
+            body: JSON.stringify({user: {username}, recoveryCode, newPassword})
+        })
+            .then(response => response.text())
+            .then(text => {
+                let response = JSON.parse(text)
+                if (response.code === 0) {
+                    alert("Password reset, you can log in with the new one now.")
+                    location.replace("log-in.html")
+                } else {
+                    alert(response.body)
+                }
+            })
+    }
+}
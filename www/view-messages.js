@@ -24,6 +24,7 @@ window.onload = async () => {
                         let recipient = message.recipient
                         let time = message.time
                         let message_text = message.text
+                        let message_id = message.id
                         let otherUser = sender
                         if (sender === username) {
                             otherUser = recipient
@@ -79,6 +80,13 @@ window.onload = async () => {
                                     })
                             }
                             message.appendChild(block)
+                            if (recipient === username) {
+                                let reportButton = document.createElement("a")
+                                reportButton.className = "button"
+                                reportButton.innerText = "Report"
+                                reportButton.onclick = () => report({kind: "message", id: message_id})
+                                message.appendChild(reportButton)
+                            }
                             inner.appendChild(message)
                         }
                     })
@@ -3,7 +3,11 @@ import {default as fillTable} from "./score-table.js"
 window.onload = async () => {
     let view_username = sessionStorage.getItem("view runs")
     document.getElementById("view_username").innerText = view_username
-    await fillTable(document.getElementById("score_table"), view_username)
+    let table = document.getElementById("score_table")
+    let difficulty = document.getElementById("difficulty")
+    difficulty.value = sessionStorage.getItem("difficulty") ?? "normal"
+    difficulty.onchange = async () => await fillTable(table, view_username, difficulty.value)
+    await fillTable(table, view_username, difficulty.value)
     let username = sessionStorage.getItem("username");
     if (sessionStorage.getItem("view runs") !== null && view_username !== username) {
         let inner = document.getElementById("inner")
@@ -23,7 +27,7 @@ window.onload = async () => {
             let messageText = textarea.value;
             if (username === null) {
                 alert("You need to be logged in to send messages.")
-            } else if (messageText.length > 500) {
+            } else if (textLength(messageText) > 500) {
                 alert("Message text needs to be 500 characters or less.")
             } else {
                 fetch("message", {
@@ -54,5 +58,10 @@ window.onload = async () => {
         }
         form.appendChild(button)
         inner.appendChild(form)
+        let reportUser = document.createElement("a")
+        reportUser.className = "button"
+        reportUser.innerText = "Report user"
+        reportUser.onclick = () => report({kind: "user", username: view_username})
+        inner.appendChild(reportUser)
     }
 }
\ No newline at end of file